@@ -0,0 +1,341 @@
+use crate::conf::ServerProfile;
+use crate::misc::QBM_API_BASE;
+use serde::de::DeserializeOwned;
+use std::io::{BufRead, BufReader};
+use util::{
+    ActiveTask, BenchmarkInfo, BenchmarkTask, CompilerInfo, Handshake, NodeInfo, RemoteServerInfo,
+    TaskEvent, TaskRecord, QBM_PROTOCOL_VERSION,
+};
+
+#[derive(Clone)]
+pub struct Client {
+    /// Server address followed by the API version prefix.
+    base_url: String,
+    secrets: Option<String>,
+}
+
+impl Client {
+    pub fn new(profile: &ServerProfile) -> Client {
+        let server = profile.address.trim_end_matches('/');
+
+        let base_url = if server.starts_with("http://") || server.starts_with("https://") {
+            format!("{}{}", server, QBM_API_BASE)
+        } else {
+            format!("http://{}{}", server, QBM_API_BASE)
+        };
+
+        Client {
+            base_url,
+            secrets: profile.secrets.clone(),
+        }
+    }
+
+    /// A request carrying the profile's secrets, if it has any.
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let request = ureq::request(method, url);
+
+        match &self.secrets {
+            Some(secrets) => request.set("Authorization", &format!("Bearer {}", secrets)),
+            None => request,
+        }
+    }
+
+    pub fn permalink(&self, task_uid: &str) -> String {
+        format!("{}/r/{}", self.base_url, task_uid)
+    }
+
+    /// Fails with a message saying which side to upgrade when the server
+    /// does not accept tasks of this client's protocol version.
+    pub fn handshake(&self) -> Result<Handshake, String> {
+        let root_url = self.base_url.trim_end_matches(QBM_API_BASE);
+
+        let handshake = match Self::fetch_handshake(&self.base_url)? {
+            Some(handshake) => handshake,
+            None => match Self::fetch_handshake(root_url)? {
+                Some(handshake) => handshake,
+                None => self.legacy_handshake()?,
+            },
+        };
+
+        handshake.check(QBM_PROTOCOL_VERSION)?;
+
+        Ok(handshake)
+    }
+
+    /// `None` if the server has no handshake under `url`.
+    fn fetch_handshake(url: &str) -> Result<Option<Handshake>, String> {
+        match ureq::get(&format!("{}/handshake", url)).call() {
+            Ok(response) => Self::read_json(response).map(Some),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Servers predating the handshake speak version 1 if they serve the
+    /// API under its prefix at all. Every version serves `/env`, reading it
+    /// runs nothing.
+    fn legacy_handshake(&self) -> Result<Handshake, String> {
+        match self
+            .request("GET", &format!("{}/env", self.base_url))
+            .call()
+        {
+            Ok(_) => Ok(Handshake {
+                protocol_version: 1,
+                min_protocol_version: 1,
+                server_version: "unknown".to_string(),
+            }),
+            Err(ureq::Error::Status(404, _)) => Err(format!(
+                "server predates {}, upgrade the server",
+                QBM_API_BASE
+            )),
+            Err(ureq::Error::Status(code, _)) => Err(format!(
+                "unable to tell the server version, {}/env answered {}",
+                self.base_url, code
+            )),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn run(&self, task: &BenchmarkTask) -> Result<BenchmarkInfo, String> {
+        let response = self
+            .request("POST", &format!("{}/run", self.base_url))
+            .send_json(task);
+
+        match response {
+            Ok(response) => Self::read_json(response),
+            Err(ureq::Error::Status(code, response)) => {
+                match Self::read_json::<BenchmarkInfo>(response) {
+                    Ok(info) => Err(format!("server rejected task ({}): {}", code, info.message)),
+                    Err(_) => Err(format!("server rejected task ({})", code)),
+                }
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn record(&self, task_uid: &str) -> Result<TaskRecord, String> {
+        match self.request("GET", &self.permalink(task_uid)).call() {
+            Ok(response) => Self::read_json(response),
+            Err(ureq::Error::Status(404, _)) => Err(format!("task {} not found", task_uid)),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn env(&self) -> Result<RemoteServerInfo, String> {
+        match self
+            .request("GET", &format!("{}/env", self.base_url))
+            .call()
+        {
+            Ok(response) => Self::read_json(response),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn compilers(&self) -> Result<Vec<CompilerInfo>, String> {
+        match self
+            .request("GET", &format!("{}/compiler", self.base_url))
+            .call()
+        {
+            Ok(response) => Self::read_json(response),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Queued and running tasks, oldest first.
+    pub fn active_tasks(&self) -> Result<Vec<ActiveTask>, String> {
+        match self
+            .request("GET", &format!("{}/tasks", self.base_url))
+            .call()
+        {
+            Ok(response) => Self::read_json(response),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// The latest `limit` results, newest first.
+    pub fn history(&self, limit: usize) -> Result<Vec<TaskRecord>, String> {
+        let response = self
+            .request("GET", &format!("{}/history", self.base_url))
+            .query("limit", &limit.to_string())
+            .call();
+
+        match response {
+            Ok(response) => Self::read_json(response),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Stops a queued or running task, `false` if the server does not know it.
+    pub fn cancel(&self, task_uid: &str) -> Result<bool, String> {
+        match self
+            .request(
+                "POST",
+                &format!("{}/tasks/{}/cancel", self.base_url, task_uid),
+            )
+            .call()
+        {
+            Ok(_) => Ok(true),
+            Err(ureq::Error::Status(404, _)) => Ok(false),
+            Err(ureq::Error::Status(401, _)) => {
+                Err("not allowed to cancel, set secrets for this server in the config".to_string())
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Workers registered at a coordinator.
+    pub fn nodes(&self) -> Result<Vec<NodeInfo>, String> {
+        match self
+            .request("GET", &format!("{}/nodes", self.base_url))
+            .call()
+        {
+            Ok(response) => Self::read_json(response),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Marks a stored result as the baseline later runs of `name` are
+    /// compared against.
+    pub fn set_baseline(&self, name: &str, task_uid: &str) -> Result<TaskRecord, String> {
+        let response = self
+            .request("PUT", &format!("{}/baselines/{}", self.base_url, name))
+            .send_json(ureq::json!({ "task_uid": task_uid }));
+
+        match response {
+            Ok(response) => Self::read_json(response),
+            Err(ureq::Error::Status(404, _)) => Err(format!("task {} not found", task_uid)),
+            Err(ureq::Error::Status(422, response)) => Err(response
+                .into_string()
+                .unwrap_or_else(|_| format!("task {} cannot be a baseline", task_uid))),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Follows the event stream of a task until it is done or `on_event`
+    /// returns `false`.
+    pub fn events<F>(&self, task_uid: &str, mut on_event: F) -> Result<(), String>
+    where
+        F: FnMut(TaskEvent) -> bool,
+    {
+        let response = self
+            .request(
+                "GET",
+                &format!("{}/tasks/{}/events", self.base_url, task_uid),
+            )
+            .call()
+            .map_err(|e| e.to_string())?;
+
+        for line in BufReader::new(response.into_reader()).lines() {
+            let line = line.map_err(|e| e.to_string())?;
+
+            let event = match line.strip_prefix("data:") {
+                Some(data) => serde_json::from_str::<TaskEvent>(data.trim()),
+                None => continue,
+            };
+
+            match event {
+                Ok(TaskEvent::Done { result }) => {
+                    on_event(TaskEvent::Done { result });
+                    break;
+                }
+                Ok(event) => {
+                    if !on_event(event) {
+                        break;
+                    }
+                }
+                Err(e) => warn!("skip unknown event: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_json<T: DeserializeOwned>(response: ureq::Response) -> Result<T, String> {
+        response
+            .into_json()
+            .map_err(|e| format!("invalid server response, {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn profile(address: &str) -> ServerProfile {
+        ServerProfile {
+            address: address.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Answers every request with the status `routes` gives its request line,
+    /// 404 for anything else.
+    fn serve(routes: &'static [(&'static str, u16, &'static str)]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = vec![0; 4096];
+                let read = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..read]);
+                let line = request.lines().next().unwrap_or_default();
+
+                let (status, body) = routes
+                    .iter()
+                    .find(|(route, ..)| line.starts_with(route))
+                    .map(|(_, status, body)| (*status, *body))
+                    .unwrap_or((404, ""));
+
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        address
+    }
+
+    const HANDSHAKE: &str =
+        r#"{"protocol_version": 2, "min_protocol_version": 1, "server_version": "0.2.0"}"#;
+
+    #[test]
+    fn retries_the_handshake_at_the_root() {
+        let server = serve(&[("GET /handshake ", 200, HANDSHAKE)]);
+        let handshake = Client::new(&profile(&server)).handshake().unwrap();
+
+        assert_eq!(handshake.server_version, "0.2.0");
+    }
+
+    #[test]
+    fn falls_back_to_version_1_for_servers_without_a_handshake() {
+        let server = serve(&[("GET /api/v1/env ", 200, "{}")]);
+        let handshake = Client::new(&profile(&server)).handshake().unwrap();
+
+        assert_eq!(handshake.protocol_version, 1);
+    }
+
+    #[test]
+    fn asks_to_upgrade_servers_without_the_api_prefix() {
+        let server = serve(&[("GET /env ", 200, "{}")]);
+        let error = Client::new(&profile(&server)).handshake().unwrap_err();
+
+        assert_eq!(error, "server predates /api/v1, upgrade the server");
+    }
+
+    #[test]
+    fn does_not_mistake_failing_servers_for_version_1() {
+        let server = serve(&[("GET /api/v1/env ", 500, "")]);
+        let error = Client::new(&profile(&server)).handshake().unwrap_err();
+
+        assert!(error.contains("unable to tell the server version"));
+    }
+}
@@ -0,0 +1,712 @@
+use crate::client::Client;
+use crate::conf::{ClientConfig, ServerProfile};
+use crate::format::{
+    compiler_rows, env_rows, open_output, output_format, result_rows, write_report, MachineResult,
+    SuiteResult,
+};
+use crate::misc::QBM_REGRESSION_EXIT_CODE;
+use crate::render::{
+    write_comparison, write_compilers, write_env, write_histogram, write_machine_comparison,
+    write_record, write_result, write_suite_summary, write_watch_run,
+};
+use crate::suite::load_suite;
+use clap::ArgMatches;
+use indicatif::{ProgressBar, ProgressStyle};
+use notify::{RecursiveMode, Watcher};
+use qbmr::{system_compilers, Engine};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use util::{
+    box_plot_svg, histogram_svg, html_report, ArgPolicy, BenchmarkInfo, BenchmarkTask,
+    NodeConstraints, TaskEvent, QBM_PROTOCOL_VERSION,
+};
+use uuid::Uuid;
+
+pub fn run(config: &ClientConfig, matches: &ArgMatches) -> Result<(), String> {
+    let (source_file, source_code) = read_source(matches)?;
+    let source_file = source_file.as_str();
+
+    if matches.is_present("all") {
+        return run_everywhere(config, matches, source_file, &source_code);
+    }
+
+    let task_uid = Uuid::new_v4().to_string();
+    let task = build_task(
+        matches,
+        &config.server,
+        &task_uid,
+        source_file,
+        &source_code,
+    )?;
+    let iterations = task.iterations;
+
+    let client = Client::new(&config.server);
+    client.handshake()?;
+    let progress = task_progress(iterations);
+
+    {
+        let client = client.clone();
+        let progress = progress.clone();
+        let task_uid = task_uid.clone();
+
+        thread::spawn(move || {
+            let follow = client.events(&task_uid, |event| {
+                match event {
+                    TaskEvent::Queued => progress.set_message("queued"),
+                    TaskEvent::Compiling => progress.set_message("compiling"),
+                    TaskEvent::Compiled { .. } => progress.set_message("compiled"),
+                    TaskEvent::Iteration { iteration, .. } => {
+                        progress.set_message("running");
+                        progress.set_position(iteration as u64);
+                    }
+                    TaskEvent::Output { .. } => {}
+                    TaskEvent::Done { .. } => progress.set_message("done"),
+                }
+
+                true
+            });
+
+            if let Err(e) = follow {
+                debug!("progress unavailable: {}", e);
+            }
+        });
+    }
+
+    let result = client.run(&task);
+    progress.finish_and_clear();
+
+    let result = result?;
+    let permalink = client.permalink(&result.task_uid);
+    let rows = result_rows("server", &[(config.name.clone(), Ok(result.clone()))]);
+
+    write_report(matches, &result, rows, |out| {
+        write_result(out, &result)?;
+
+        if matches.is_present("histogram") {
+            write_histogram(out, &result.timings)?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "Permalink: {}", permalink)
+    })?;
+    export_svg(matches, || histogram_svg(&result.task_uid, &result.timings))?;
+
+    // Lets nightly jobs fail on a regression without parsing the output.
+    if result.comparison.is_some_and(|c| c.regression) {
+        process::exit(QBM_REGRESSION_EXIT_CODE);
+    }
+
+    Ok(())
+}
+
+/// Benchmarks on this machine with the qbmr engine instead of a server.
+pub fn run_local(matches: &ArgMatches) -> Result<(), String> {
+    let (source_file, source_code) = read_source(matches)?;
+
+    let compiler = matches
+        .value_of("compiler")
+        .ok_or("--compiler is required with --local")?;
+    let compiler_version = matches.value_of("compiler-version");
+    let system_compiler = system_compilers()
+        .into_iter()
+        .find(|c| c.info.name == compiler && compiler_version.is_none_or(|v| v == c.info.version))
+        .ok_or_else(|| format!("no local compiler matches {}", compiler))?;
+
+    // The version may be left out, there is only one of each compiler.
+    let profile = ServerProfile {
+        compiler_version: Some(system_compiler.info.version.clone()),
+        ..Default::default()
+    };
+    let task_uid = Uuid::new_v4().to_string();
+    let task = build_task(matches, &profile, &task_uid, &source_file, &source_code)?;
+
+    let root = env::temp_dir().join("qbmc");
+    let source_directory = root.join("source");
+    let build_directory = root.join("build");
+    let engine = Engine::new(
+        &[system_compiler.config()],
+        &ArgPolicy::default(),
+        &source_directory.to_string_lossy(),
+        &build_directory.to_string_lossy(),
+    )?;
+
+    let progress = task_progress(task.iterations);
+    progress.set_message("running");
+
+    let result = engine.benchmark(&task, |iteration, _| {
+        progress.set_position(iteration as u64)
+    });
+    progress.finish_and_clear();
+
+    let rows = result_rows("server", &[("local".to_string(), Ok(result.clone()))]);
+    write_report(matches, &result, rows, |out| {
+        write_result(out, &result)?;
+
+        if matches.is_present("histogram") {
+            write_histogram(out, &result.timings)?;
+        }
+
+        Ok(())
+    })?;
+
+    export_svg(matches, || histogram_svg(&result.task_uid, &result.timings))
+}
+
+/// File name and content of the `run` source file.
+fn read_source(matches: &ArgMatches) -> Result<(String, String), String> {
+    let source_path = matches.value_of("FILE").unwrap();
+    let source_code = fs::read_to_string(source_path)
+        .map_err(|e| format!("unable to read {}, {}", source_path, e))?;
+    let source_file = Path::new(source_path)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .ok_or_else(|| format!("invalid source file {}", source_path))?;
+
+    Ok((source_file.to_string(), source_code))
+}
+
+/// Builds a task from the `run` arguments, falling back to the defaults of
+/// `profile` for the compiler and its flags.
+fn build_task(
+    matches: &ArgMatches,
+    profile: &ServerProfile,
+    task_uid: &str,
+    source_file: &str,
+    source_code: &str,
+) -> Result<BenchmarkTask, String> {
+    let iterations = parse_iterations(matches)?;
+    let compiler = matches
+        .value_of("compiler")
+        .or(profile.compiler.as_deref())
+        .ok_or("no compiler given and the server profile has no default")?;
+    let compiler_version = matches
+        .value_of("compiler-version")
+        .or(profile.compiler_version.as_deref())
+        .ok_or("no compiler version given and the server profile has no default")?;
+    let compiler_args = matches
+        .value_of("args")
+        .or(profile.args.as_deref())
+        .unwrap_or("");
+    let tags = matches
+        .values_of("tag")
+        .map(|t| t.map(|t| t.to_string()).collect())
+        .unwrap_or_default();
+
+    Ok(BenchmarkTask {
+        protocol_version: QBM_PROTOCOL_VERSION,
+        task_uid: task_uid.into(),
+        compiler: compiler.into(),
+        compiler_version: compiler_version.into(),
+        source_file: source_file.into(),
+        source_code: source_code.into(),
+        compiler_args: compiler_args.into(),
+        assembly: false,
+        iterations,
+        name: matches.value_of("name").map(|n| n.to_string()),
+        user: matches.value_of("user").map(|u| u.to_string()),
+        tags,
+        constraints: NodeConstraints {
+            cpu_vendor: matches.value_of("cpu-vendor").map(|v| v.to_string()),
+            cpu_brand: matches.value_of("cpu-brand").map(|b| b.to_string()),
+            node: None,
+        },
+    })
+}
+
+fn parse_iterations(matches: &ArgMatches) -> Result<u32, String> {
+    match matches.value_of("iterations") {
+        Some(n) => n
+            .parse::<u32>()
+            .map_err(|_| format!("invalid iterations {}", n)),
+        None => Ok(1),
+    }
+}
+
+/// Runs the task on every server profile, or on every node of the selected
+/// server when one is picked with `--server` or it is the only profile, and
+/// compares the machines.
+fn run_everywhere(
+    config: &ClientConfig,
+    matches: &ArgMatches,
+    source_file: &str,
+    source_code: &str,
+) -> Result<(), String> {
+    // Reject bad arguments once instead of once per machine.
+    parse_iterations(matches)?;
+    output_format(matches)?;
+
+    let targets = if config.explicit || config.profiles.len() < 2 {
+        let client = Client::new(&config.server);
+        let nodes = client.nodes()?;
+
+        if nodes.is_empty() {
+            return Err(format!("no nodes registered at {}", config.name));
+        }
+
+        nodes
+            .into_iter()
+            .map(|n| {
+                let label = format!("{}/{}", config.name, n.address);
+                (label, client.clone(), &config.server, Some(n.address))
+            })
+            .collect::<Vec<_>>()
+    } else {
+        config
+            .profiles
+            .iter()
+            .map(|(name, profile)| (name.clone(), Client::new(profile), profile, None))
+            .collect()
+    };
+
+    eprintln!("Running on {} machines...", targets.len());
+
+    let results = thread::scope(|scope| {
+        let runs = targets
+            .iter()
+            .map(|(_, client, profile, node)| {
+                scope.spawn(move || {
+                    let task_uid = Uuid::new_v4().to_string();
+                    let mut task =
+                        build_task(matches, profile, &task_uid, source_file, source_code)?;
+                    task.constraints.node = node.clone();
+
+                    client.handshake()?;
+                    client.run(&task)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        runs.into_iter()
+            .zip(&targets)
+            .map(|(run, (server, ..))| {
+                let result = run
+                    .join()
+                    .unwrap_or_else(|_| Err("client thread panicked".to_string()));
+
+                (server.clone(), result)
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let machines = results
+        .iter()
+        .map(|(server, result)| MachineResult::new(server, result))
+        .collect::<Vec<_>>();
+
+    write_report(matches, &machines, result_rows("server", &results), |out| {
+        write_machine_comparison(out, &results)
+    })
+}
+
+fn task_progress(iterations: u32) -> ProgressBar {
+    let progress = ProgressBar::new(iterations as u64);
+
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner} {msg:10} [{bar:40}] {pos}/{len} {elapsed}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    progress.enable_steady_tick(Duration::from_millis(100));
+    progress.set_message("submitting");
+
+    progress
+}
+
+pub fn show(config: &ClientConfig, matches: &ArgMatches) -> Result<(), String> {
+    let task_uid = matches.value_of("UID").unwrap();
+    let client = Client::new(&config.server);
+
+    let record = client.record(task_uid)?;
+    let timings = &record.result.timings;
+    let compiler = format!("{} {}", record.compiler, record.compiler_version);
+    let rows = result_rows("compiler", &[(compiler, Ok(record.result.clone()))]);
+
+    write_report(matches, &record, rows, |out| {
+        write_record(out, &record)?;
+
+        if matches.is_present("histogram") {
+            write_histogram(out, timings)?;
+        }
+
+        Ok(())
+    })?;
+
+    export_svg(matches, || histogram_svg(task_uid, timings))
+}
+
+/// Two stored results side by side, `A` is the one they are compared to.
+pub fn compare(config: &ClientConfig, matches: &ArgMatches) -> Result<(), String> {
+    let client = Client::new(&config.server);
+    let base = client.record(matches.value_of("A").unwrap())?;
+    let other = client.record(matches.value_of("B").unwrap())?;
+
+    write_comparison(&mut io::stdout(), &base, &other).map_err(|e| e.to_string())?;
+
+    export_svg(matches, || {
+        let title = format!("{} vs {}", base.task_uid, other.task_uid);
+        box_plot_svg(
+            &title,
+            &[("A", &base.result.timings), ("B", &other.result.timings)],
+        )
+    })
+}
+
+/// A self-contained HTML page of stored results for people without the CLI.
+pub fn report(config: &ClientConfig, matches: &ArgMatches) -> Result<(), String> {
+    let client = Client::new(&config.server);
+    let records = matches
+        .values_of("UID")
+        .unwrap()
+        .map(|task_uid| client.record(task_uid))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = open_output(matches)?;
+
+    out.write_all(html_report(&records).as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("unable to write report, {}", e))
+}
+
+/// Writes the chart to `--svg` when it is given.
+fn export_svg<F: FnOnce() -> String>(matches: &ArgMatches, svg: F) -> Result<(), String> {
+    match matches.value_of("svg") {
+        Some(path) => {
+            fs::write(path, svg()).map_err(|e| format!("unable to write {}, {}", path, e))
+        }
+        None => Ok(()),
+    }
+}
+
+pub fn baseline(config: &ClientConfig, matches: &ArgMatches) -> Result<(), String> {
+    let name = matches.value_of("NAME").unwrap();
+    let task_uid = matches.value_of("UID").unwrap();
+    let client = Client::new(&config.server);
+
+    let record = client.set_baseline(name, task_uid)?;
+    println!("Baseline of {} is now {}", name, record.task_uid);
+
+    Ok(())
+}
+
+pub fn env(config: &ClientConfig, matches: &ArgMatches) -> Result<(), String> {
+    let env = Client::new(&config.server).env()?;
+
+    write_report(matches, &env, env_rows(&env), |out| write_env(out, &env))
+}
+
+pub fn compilers(config: &ClientConfig, matches: &ArgMatches) -> Result<(), String> {
+    let compilers = Client::new(&config.server).compilers()?;
+
+    write_report(matches, &compilers, compiler_rows(&compilers), |out| {
+        write_compilers(out, &compilers)
+    })
+}
+
+pub fn suite(config: &ClientConfig, matches: &ArgMatches) -> Result<(), String> {
+    match matches.subcommand() {
+        ("run", Some(matches)) => suite_run(config, matches),
+        _ => Ok(()),
+    }
+}
+
+/// Runs the benchmarks of a manifest one after another and summarizes them.
+fn suite_run(config: &ClientConfig, matches: &ArgMatches) -> Result<(), String> {
+    let suite = load_suite(matches.value_of("MANIFEST").unwrap())?;
+    let tags = matches
+        .values_of("filter")
+        .map(|t| t.collect::<Vec<_>>())
+        .unwrap_or_default();
+    let benchmarks = suite.select(&tags);
+    output_format(matches)?;
+
+    if benchmarks.is_empty() {
+        return Err("no benchmark matches the filter".to_string());
+    }
+
+    let client = Client::new(&config.server);
+    client.handshake()?;
+
+    for benchmark in &benchmarks {
+        if let Some(baseline) = &benchmark.baseline {
+            client.set_baseline(&benchmark.name, baseline)?;
+        }
+    }
+
+    let results = benchmarks
+        .iter()
+        .enumerate()
+        .map(|(i, benchmark)| {
+            eprintln!("[{}/{}] {}", i + 1, benchmarks.len(), benchmark.name);
+
+            let task_uid = Uuid::new_v4().to_string();
+            let result = benchmark
+                .read_source()
+                .and_then(|(source_file, source_code)| {
+                    let task = benchmark.task(
+                        &suite.defaults,
+                        &config.server,
+                        &task_uid,
+                        &source_file,
+                        &source_code,
+                    )?;
+
+                    client.run(&task)
+                });
+
+            (benchmark.name.clone(), result)
+        })
+        .collect::<Vec<_>>();
+
+    let entries = results
+        .iter()
+        .map(|(name, result)| SuiteResult::new(name, result))
+        .collect::<Vec<_>>();
+
+    write_report(
+        matches,
+        &entries,
+        result_rows("benchmark", &results),
+        |out| write_suite_summary(out, &results),
+    )?;
+
+    let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+    let regressed = results.iter().any(|(_, r)| {
+        r.as_ref()
+            .is_ok_and(|info| info.comparison.as_ref().is_some_and(|c| c.regression))
+    });
+
+    if failed > 0 {
+        return Err(format!("{} of {} benchmarks failed", failed, results.len()));
+    }
+
+    if regressed {
+        process::exit(QBM_REGRESSION_EXIT_CODE);
+    }
+
+    Ok(())
+}
+
+enum WatchEvent {
+    Changed,
+    Finished(String, Result<Box<BenchmarkInfo>, String>),
+}
+
+/// Benchmarks `FILE` again whenever it is saved and shows each run next to
+/// the previous one, a run still in flight is cancelled by the next.
+pub fn watch(config: &ClientConfig, matches: &ArgMatches) -> Result<(), String> {
+    let source_path = Path::new(matches.value_of("FILE").unwrap());
+    let debounce = match matches.value_of("debounce") {
+        Some(ms) => ms
+            .parse::<u64>()
+            .map(Duration::from_millis)
+            .map_err(|_| format!("invalid debounce {}", ms))?,
+        None => Duration::from_millis(300),
+    };
+    parse_iterations(matches)?;
+
+    // Editors often save by replacing the file, so its directory is watched.
+    let directory = source_path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let file_name = source_path
+        .file_name()
+        .ok_or_else(|| format!("invalid source file {}", source_path.display()))?
+        .to_owned();
+
+    let (sender, receiver) = mpsc::channel();
+
+    let mut watcher = {
+        let sender = sender.clone();
+
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if event.kind.is_access() => {}
+            Ok(event) => {
+                if event
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == Some(&file_name))
+                {
+                    let _ = sender.send(WatchEvent::Changed);
+                }
+            }
+            Err(e) => warn!("watch failed: {}", e),
+        })
+        .map_err(|e| format!("unable to watch {}, {}", source_path.display(), e))?
+    };
+
+    watcher
+        .watch(directory, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("unable to watch {}, {}", source_path.display(), e))?;
+
+    eprintln!("Watching {}, press Ctrl-C to stop", source_path.display());
+
+    let client = Client::new(&config.server);
+    client.handshake()?;
+
+    thread::scope(|scope| {
+        let start = |task_uid: &str| {
+            let (source_file, source_code) = read_source(matches)?;
+            let task_uid = task_uid.to_string();
+            let client = &client;
+            let sender = sender.clone();
+
+            scope.spawn(move || {
+                let result = build_task(
+                    matches,
+                    &config.server,
+                    &task_uid,
+                    &source_file,
+                    &source_code,
+                )
+                .and_then(|task| client.run(&task))
+                .map(Box::new);
+
+                let _ = sender.send(WatchEvent::Finished(task_uid, result));
+            });
+
+            Ok(())
+        };
+
+        let cancel = |task_uid: &str| {
+            if let Err(e) = client.cancel(task_uid) {
+                warn!("unable to cancel {}: {}", task_uid, e);
+            }
+        };
+
+        let show = |run, result: &BenchmarkInfo, previous: Option<(u32, &BenchmarkInfo)>| {
+            write_watch_run(&mut io::stdout(), run, result, previous).map_err(|e| e.to_string())
+        };
+
+        watch_loop(&receiver, debounce, start, cancel, show)
+    })
+}
+
+/// Starts a run once no change arrived for `debounce`, and the run still in
+/// flight is cancelled and its result dropped. Returns when every sender of
+/// `receiver` is gone.
+fn watch_loop<S, C, W>(
+    receiver: &mpsc::Receiver<WatchEvent>,
+    debounce: Duration,
+    mut start: S,
+    mut cancel: C,
+    mut show: W,
+) -> Result<(), String>
+where
+    S: FnMut(&str) -> Result<(), String>,
+    C: FnMut(&str),
+    W: FnMut(u32, &BenchmarkInfo, Option<(u32, &BenchmarkInfo)>) -> Result<(), String>,
+{
+    let mut changed = Some(Instant::now());
+    let mut running: Option<String> = None;
+    let mut previous: Option<(u32, BenchmarkInfo)> = None;
+    let mut runs = 0;
+
+    loop {
+        let event = match changed {
+            Some(at) => {
+                receiver.recv_timeout((at + debounce).saturating_duration_since(Instant::now()))
+            }
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match event {
+            Ok(WatchEvent::Changed) => changed = Some(Instant::now()),
+            Ok(WatchEvent::Finished(task_uid, result)) => {
+                // Results of cancelled runs are dropped.
+                if running.as_deref() != Some(task_uid.as_str()) {
+                    continue;
+                }
+
+                running = None;
+
+                let result = result
+                    .map(|r| *r)
+                    .unwrap_or_else(|e| BenchmarkInfo::failed(&task_uid, e));
+                let last = previous.as_ref().map(|(run, info)| (*run, info));
+
+                show(runs, &result, last)?;
+
+                if !result.timings.is_empty() {
+                    previous = Some((runs, result));
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                changed = None;
+
+                let task_uid = Uuid::new_v4().to_string();
+
+                if let Err(e) = start(&task_uid) {
+                    warn!("{}", e);
+                    continue;
+                }
+
+                runs += 1;
+
+                if let Some(superseded) = running.replace(task_uid) {
+                    cancel(&superseded);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished(task_uid: &str) -> WatchEvent {
+        let result = BenchmarkInfo {
+            timings: vec![100],
+            ..BenchmarkInfo::new(task_uid)
+        };
+
+        WatchEvent::Finished(task_uid.to_string(), Ok(Box::new(result)))
+    }
+
+    #[test]
+    fn debounces_changes_and_supersedes_runs() {
+        let (sender, receiver) = mpsc::channel();
+        let mut sender = Some(sender);
+        let mut started = vec![];
+        let mut cancelled = vec![];
+        let mut shown = vec![];
+
+        let start = |task_uid: &str| {
+            started.push(task_uid.to_string());
+
+            if started.len() == 1 {
+                // Saves while the first run is in flight make one new run.
+                for _ in 0..3 {
+                    sender.as_ref().unwrap().send(WatchEvent::Changed).unwrap();
+                }
+            } else {
+                // The superseded run finishing late is dropped.
+                let sender = sender.take().unwrap();
+                sender.send(finished(&started[0])).unwrap();
+                sender.send(finished(&started[1])).unwrap();
+            }
+
+            Ok(())
+        };
+        let cancel = |task_uid: &str| cancelled.push(task_uid.to_string());
+        let show = |run, result: &BenchmarkInfo, previous: Option<(u32, &BenchmarkInfo)>| {
+            shown.push((run, result.task_uid.clone(), previous.is_some()));
+            Ok(())
+        };
+
+        watch_loop(&receiver, Duration::from_millis(20), start, cancel, show).unwrap();
+
+        assert_eq!(started.len(), 2);
+        assert_eq!(cancelled, [started[0].clone()]);
+        assert_eq!(shown, [(2, started[1].clone(), false)]);
+    }
+}
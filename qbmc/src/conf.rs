@@ -0,0 +1,148 @@
+use figment::providers::{Env, Format, Toml};
+use figment::Figment;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Name of the profile made from `[client] server = ...`.
+const LEGACY_PROFILE: &str = "default";
+
+/// A `[servers.<name>]` table, the optional fields are defaults for `run`.
+#[derive(Deserialize, Clone, Default)]
+pub struct ServerProfile {
+    pub address: String,
+    /// Sent as the bearer token, defaults to `[client] secrets`.
+    pub secrets: Option<String>,
+    pub compiler: Option<String>,
+    pub compiler_version: Option<String>,
+    pub args: Option<String>,
+}
+
+pub struct ClientConfig {
+    /// Name of the profile selected with `--server` or the default one.
+    pub name: String,
+    pub server: ServerProfile,
+    /// Set when the profile was picked with `--server`.
+    pub explicit: bool,
+    pub profiles: BTreeMap<String, ServerProfile>,
+}
+
+#[derive(Deserialize, Default)]
+struct ClientSection {
+    server: Option<String>,
+    secrets: Option<String>,
+    default_server: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    client: ClientSection,
+    #[serde(default)]
+    servers: BTreeMap<String, ServerProfile>,
+}
+
+/// Reads the config file, overridden by `QBMC_` variables such as
+/// `QBMC_CLIENT__DEFAULT_SERVER` or `QBMC_SERVERS__LAB__ADDRESS`.
+pub fn setup_config(config_file_path: &str, server: Option<&str>) -> Result<ClientConfig, String> {
+    let figment = Figment::new()
+        .merge(Toml::file(config_file_path))
+        .merge(Env::prefixed("QBMC_").split("__"));
+
+    from_figment(figment, server).map_err(|e| format!("invalid config {}, {}", config_file_path, e))
+}
+
+fn from_figment(figment: Figment, server: Option<&str>) -> Result<ClientConfig, String> {
+    let file = figment.extract::<ConfigFile>().map_err(|e| e.to_string())?;
+
+    let mut profiles = file.servers;
+
+    if let Some(address) = file.client.server {
+        profiles
+            .entry(LEGACY_PROFILE.to_string())
+            .or_insert(ServerProfile {
+                address,
+                ..Default::default()
+            });
+    }
+
+    for profile in profiles.values_mut() {
+        if profile.secrets.is_none() {
+            profile.secrets = file.client.secrets.clone();
+        }
+    }
+
+    let name = match (server, &file.client.default_server) {
+        (Some(server), _) => server.to_string(),
+        (None, Some(default_server)) => default_server.clone(),
+        (None, None) if profiles.len() == 1 => profiles.keys().next().unwrap().clone(),
+        (None, None) if profiles.contains_key(LEGACY_PROFILE) => LEGACY_PROFILE.to_string(),
+        (None, None) => {
+            return Err(
+                "no server configured, set [client] default_server or pass --server".to_string(),
+            )
+        }
+    };
+
+    let server_profile = profiles.get(&name).cloned().ok_or_else(|| {
+        format!(
+            "unknown server {}, configured: {}",
+            name,
+            profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+        )
+    })?;
+
+    Ok(ClientConfig {
+        name,
+        server: server_profile,
+        explicit: server.is_some(),
+        profiles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILES: &str = r#"
+        [client]
+        default_server = "lab-intel"
+        secrets = "shared"
+
+        [servers.lab-amd]
+        address = "10.0.0.2:9000"
+        compiler = "g++"
+        secrets = "amd"
+
+        [servers.lab-intel]
+        address = "10.0.0.3:9000"
+    "#;
+
+    fn load(toml: &str, server: Option<&str>) -> Result<ClientConfig, String> {
+        from_figment(Figment::from(Toml::string(toml)), server)
+    }
+
+    #[test]
+    fn selects_profiles() {
+        let config = load(PROFILES, None).unwrap();
+        assert_eq!(config.name, "lab-intel");
+        assert_eq!(config.server.address, "10.0.0.3:9000");
+        assert_eq!(config.server.secrets.as_deref(), Some("shared"));
+        assert!(!config.explicit);
+
+        let config = load(PROFILES, Some("lab-amd")).unwrap();
+        assert_eq!(config.server.compiler.as_deref(), Some("g++"));
+        assert_eq!(config.server.secrets.as_deref(), Some("amd"));
+        assert!(config.explicit);
+
+        assert!(load(PROFILES, Some("lab-arm")).is_err());
+    }
+
+    #[test]
+    fn reads_legacy_client_server() {
+        let config = load("[client]\nserver = '127.0.0.1:9000'\nsecrets = 'x'\n", None).unwrap();
+
+        assert_eq!(config.name, "default");
+        assert_eq!(config.server.address, "127.0.0.1:9000");
+        assert_eq!(config.server.secrets.as_deref(), Some("x"));
+    }
+}
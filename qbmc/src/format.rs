@@ -0,0 +1,339 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+use util::{BenchmarkInfo, CompilerInfo, RemoteServerInfo};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    /// For people, the default.
+    Table,
+    /// The `util` types as the server sends them.
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Format, String> {
+        match format {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "markdown" => Ok(Format::Markdown),
+            _ => Err(format!("unknown format {}", format)),
+        }
+    }
+}
+
+/// Flat rows for the CSV and Markdown formats, the header is part of the
+/// schema and only ever grows at the end.
+pub struct Rows {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Rows {
+    pub fn new(header: &[&str]) -> Rows {
+        Rows {
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub fn write_aligned(&self, out: &mut dyn Write) -> io::Result<()> {
+        let widths = (0..self.header.len())
+            .map(|i| {
+                self.rows
+                    .iter()
+                    .chain([&self.header])
+                    .map(|r| r[i].len())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+
+        for row in [&self.header].into_iter().chain(&self.rows) {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+
+            writeln!(out, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_csv(&self, out: &mut dyn Write) -> io::Result<()> {
+        for row in [&self.header].into_iter().chain(&self.rows) {
+            let line = row
+                .iter()
+                .map(|cell| csv_field(cell))
+                .collect::<Vec<_>>()
+                .join(",");
+
+            writeln!(out, "{}", line)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_markdown(&self, out: &mut dyn Write) -> io::Result<()> {
+        let cells = |row: &[String]| {
+            row.iter()
+                .map(|cell| cell.replace('|', "\\|").replace('\n', " "))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        };
+
+        writeln!(out, "| {} |", cells(&self.header))?;
+        writeln!(out, "|{}", " --- |".repeat(self.header.len()))?;
+
+        for row in &self.rows {
+            writeln!(out, "| {} |", cells(row))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn csv_field(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// JSON schema of a `run --all` entry.
+#[derive(Serialize)]
+pub struct MachineResult<'a> {
+    pub server: &'a str,
+    pub result: Option<&'a BenchmarkInfo>,
+    pub error: Option<&'a str>,
+}
+
+impl<'a> MachineResult<'a> {
+    pub fn new(server: &'a str, result: &'a Result<BenchmarkInfo, String>) -> MachineResult<'a> {
+        MachineResult {
+            server,
+            result: result.as_ref().ok(),
+            error: result.as_ref().err().map(|e| e.as_str()),
+        }
+    }
+}
+
+/// JSON schema of a `suite run` entry.
+#[derive(Serialize)]
+pub struct SuiteResult<'a> {
+    pub benchmark: &'a str,
+    pub result: Option<&'a BenchmarkInfo>,
+    pub error: Option<&'a str>,
+}
+
+impl<'a> SuiteResult<'a> {
+    pub fn new(benchmark: &'a str, result: &'a Result<BenchmarkInfo, String>) -> SuiteResult<'a> {
+        SuiteResult {
+            benchmark,
+            result: result.as_ref().ok(),
+            error: result.as_ref().err().map(|e| e.as_str()),
+        }
+    }
+}
+
+/// One row per result, the first column is named `label`, e.g. the server
+/// it ran on.
+pub fn result_rows(label: &str, results: &[(String, Result<BenchmarkInfo, String>)]) -> Rows {
+    let mut rows = Rows::new(&[
+        label,
+        "task_uid",
+        "node",
+        "cpu_brand",
+        "fingerprint",
+        "iterations",
+        "mean_ns",
+        "min_ns",
+        "max_ns",
+        "baseline_change",
+        "regression",
+        "message",
+    ]);
+
+    for (server, result) in results {
+        let info = match result {
+            Ok(info) => info,
+            Err(e) => {
+                // Only the label and the message, which is the last column.
+                let mut row = vec![server.clone()];
+                row.resize(rows.header.len() - 1, "".to_string());
+                row.push(e.clone());
+                rows.push(row);
+                continue;
+            }
+        };
+
+        let timings = &info.timings;
+        let mean =
+            (!timings.is_empty()).then(|| timings.iter().sum::<u64>() / timings.len() as u64);
+        let optional = |v: Option<String>| v.unwrap_or_default();
+
+        rows.push(vec![
+            server.clone(),
+            info.task_uid.clone(),
+            optional(info.node.clone()),
+            optional(info.fingerprint.as_ref().map(|f| f.cpu_brand.clone())),
+            optional(info.fingerprint.as_ref().map(|f| f.id())),
+            timings.len().to_string(),
+            optional(mean.map(|m| m.to_string())),
+            optional(timings.iter().min().map(|m| m.to_string())),
+            optional(timings.iter().max().map(|m| m.to_string())),
+            optional(info.comparison.as_ref().map(|c| format!("{:.4}", c.change))),
+            optional(info.comparison.as_ref().map(|c| c.regression.to_string())),
+            info.message.trim().to_string(),
+        ]);
+    }
+
+    rows
+}
+
+pub fn env_rows(env: &RemoteServerInfo) -> Rows {
+    let mut rows = Rows::new(&[
+        "platform",
+        "os_version",
+        "kernel_version",
+        "cpu_vendor",
+        "cpu_brand",
+        "cpu_cores",
+        "cpu_logical_cores",
+        "total_memory",
+        "total_swap",
+        "readiness",
+    ]);
+
+    rows.push(vec![
+        env.platform.clone(),
+        env.os_version.clone(),
+        env.kernel_version.clone(),
+        env.cpu_vendor.clone(),
+        env.cpu_brand.trim().to_string(),
+        env.cpu_cores.to_string(),
+        env.cpu_logical_cores.to_string(),
+        env.total_memory.clone(),
+        env.total_swap.clone(),
+        env.readiness.join("; "),
+    ]);
+
+    rows
+}
+
+pub fn compiler_rows(compilers: &[CompilerInfo]) -> Rows {
+    let mut rows = Rows::new(&["name", "version", "target", "thread_model"]);
+
+    for compiler in compilers {
+        rows.push(vec![
+            compiler.name.clone(),
+            compiler.version.clone(),
+            compiler.target.clone().unwrap_or_default(),
+            compiler.thread_model.clone().unwrap_or_default(),
+        ]);
+    }
+
+    rows
+}
+
+/// Writes `value` in `format` to `--output` or stdout, `human` renders the
+/// table format.
+pub fn write_report<T, H>(
+    matches: &clap::ArgMatches,
+    value: &T,
+    rows: Rows,
+    human: H,
+) -> Result<(), String>
+where
+    T: Serialize,
+    H: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    let format = output_format(matches)?;
+    let mut out = open_output(matches)?;
+
+    match format {
+        Format::Table => human(&mut out),
+        Format::Json => serde_json::to_writer_pretty(&mut out, value)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(out)),
+        Format::Csv => rows.write_csv(&mut out),
+        Format::Markdown => rows.write_markdown(&mut out),
+    }
+    .and_then(|_| out.flush())
+    .map_err(|e| format!("unable to write output, {}", e))
+}
+
+/// `--output` or stdout.
+pub fn open_output(matches: &clap::ArgMatches) -> Result<Box<dyn Write>, String> {
+    match matches.value_of("output") {
+        Some(path) => Ok(Box::new(BufWriter::new(
+            File::create(path).map_err(|e| format!("unable to create {}, {}", path, e))?,
+        ))),
+        None => Ok(Box::new(io::stdout())),
+    }
+}
+
+pub fn output_format(matches: &clap::ArgMatches) -> Result<Format, String> {
+    matches.value_of("format").unwrap_or("table").parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Rows {
+        let mut rows = Rows::new(&["name", "flags"]);
+        rows.push(vec!["g++".to_string(), "-O2 -DX=\"a,b\"".to_string()]);
+        rows.push(vec!["a|b".to_string(), "".to_string()]);
+        rows
+    }
+
+    fn render(write: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> String {
+        let mut out = vec![];
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(
+            render(|out| rows().write_csv(out)),
+            "name,flags\ng++,\"-O2 -DX=\"\"a,b\"\"\"\na|b,\n"
+        );
+    }
+
+    #[test]
+    fn escapes_markdown_cells() {
+        assert_eq!(
+            render(|out| rows().write_markdown(out)),
+            "| name | flags |\n| --- | --- |\n| g++ | -O2 -DX=\"a,b\" |\n| a\\|b |  |\n"
+        );
+    }
+
+    #[test]
+    fn flattens_failed_results() {
+        let results = vec![("lab".to_string(), Err("connection refused".to_string()))];
+        let csv = render(|out| result_rows("server", &results).write_csv(out));
+
+        let header = csv.lines().next().unwrap();
+        let row = csv.lines().nth(1).unwrap();
+
+        assert!(header.ends_with(",message"));
+        assert_eq!(row.matches(',').count(), header.matches(',').count());
+        assert!(row.starts_with("lab,,") && row.ends_with(",connection refused"));
+    }
+}
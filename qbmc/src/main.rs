@@ -1,7 +1,16 @@
+mod client;
+mod command;
+mod conf;
+mod format;
 mod misc;
+mod render;
+mod suite;
+mod tui;
 
+use crate::conf::setup_config;
 use crate::misc::QBM_DEFAULT_CONFIG_FILE_PATH;
-use clap::{App, Arg, SubCommand};
+use clap::{App, AppSettings, Arg, SubCommand};
+use std::process;
 
 #[macro_use]
 extern crate util;
@@ -21,6 +30,31 @@ fn main() {
                 .help("Sets a custom config file")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("server")
+                .long("server")
+                .value_name("NAME")
+                .help("Server profile to use, see [servers.<NAME>] in the config")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .help("Output format of run, suite run, show, env and compilers")
+                .possible_values(&["table", "json", "csv", "markdown"])
+                .default_value("table")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .help("Write the output of run, suite run, show, env, compilers and report to a file")
+                .takes_value(true)
+                .global(true),
+        )
         .subcommand(
             SubCommand::with_name("server")
                 .about("Run qbm in server mode")
@@ -32,15 +66,240 @@ fn main() {
                         .long("port")
                         .help("Sets server port")
                         .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Submit a source file to the server and print the result")
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("Source file to benchmark")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("compiler")
+                        .short("c")
+                        .long("compiler")
+                        .help("Compiler name, e.g. g++, defaults to the server profile's")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("compiler-version")
+                        .long("compiler-version")
+                        .help("Compiler version, e.g. 11.2.0, defaults to the server profile's")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("args")
+                        .short("a")
+                        .long("args")
+                        .help("Compiler arguments, defaults to the server profile's")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .help("Benchmark name, compared against its baseline")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("user")
+                        .short("u")
+                        .long("user")
+                        .help("User the result is recorded for")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .short("t")
+                        .long("tag")
+                        .help("Tag the result, may be repeated")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("cpu-vendor")
+                        .long("cpu-vendor")
+                        .help("Only run on cluster nodes with this CPU vendor, e.g. AMD")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("cpu-brand")
+                        .long("cpu-brand")
+                        .help("Only run on cluster nodes with this CPU brand, e.g. EPYC")
+                        .takes_value(true),
+                )
+                .arg(Arg::with_name("all").long("all").help(
+                    "Run on every server profile, or every node of --server, and compare them",
+                ))
+                .arg(
+                    Arg::with_name("local")
+                        .long("local")
+                        .help("Run on this machine with locally installed compilers, no server needed")
+                        .conflicts_with("all"),
+                )
+                .arg(
+                    Arg::with_name("iterations")
+                        .short("n")
+                        .long("iterations")
+                        .help("Number of times the program is run")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("histogram")
+                        .long("histogram")
+                        .help("Show a histogram of the per-iteration timings"),
+                )
+                .arg(
+                    Arg::with_name("svg")
+                        .long("svg")
+                        .value_name("FILE")
+                        .help("Write the histogram as SVG")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("Benchmark a source file again whenever it is saved")
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("Source file to watch")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("compiler")
+                        .short("c")
+                        .long("compiler")
+                        .help("Compiler name, e.g. g++, defaults to the server profile's")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("compiler-version")
+                        .long("compiler-version")
+                        .help("Compiler version, e.g. 11.2.0, defaults to the server profile's")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("args")
+                        .short("a")
+                        .long("args")
+                        .help("Compiler arguments, defaults to the server profile's")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("iterations")
+                        .short("n")
+                        .long("iterations")
+                        .help("Number of times the program is run")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("debounce")
+                        .long("debounce")
+                        .value_name("MS")
+                        .help("Wait for saves to settle this long, 300 by default")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Show a stored result")
+                .arg(
+                    Arg::with_name("UID")
+                        .help("Task uid printed by run")
+                        .required(true),
                 )
                 .arg(
-                    Arg::with_name("secrets")
-                        .short("s")
-                        .long("secrets")
-                        .help("Secrets to authorization")
+                    Arg::with_name("histogram")
+                        .long("histogram")
+                        .help("Show a histogram of the per-iteration timings"),
+                )
+                .arg(
+                    Arg::with_name("svg")
+                        .long("svg")
+                        .value_name("FILE")
+                        .help("Write the histogram as SVG")
                         .takes_value(true),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("Compare two stored results with box plots of their timings")
+                .arg(
+                    Arg::with_name("A")
+                        .help("Task uid the other result is compared to")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("B")
+                        .help("Task uid of the other result")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("svg")
+                        .long("svg")
+                        .value_name("FILE")
+                        .help("Write the box plots as SVG")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Write stored results as a self-contained HTML page, see --output")
+                .arg(
+                    Arg::with_name("UID")
+                        .help("Task uids printed by run")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("baseline")
+                .about("Mark a stored result as the baseline of a benchmark name")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Benchmark name given to run --name")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("UID")
+                        .help("Task uid printed by run")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("suite")
+                .about("Run the benchmarks listed in a manifest")
+                .subcommand(
+                    SubCommand::with_name("run")
+                        .about("Run every benchmark of the manifest and print a summary")
+                        .arg(
+                            Arg::with_name("MANIFEST")
+                                .help("Suite manifest")
+                                .default_value("qbm.toml"),
+                        )
+                        .arg(
+                            Arg::with_name("filter")
+                                .long("filter")
+                                .value_name("TAG")
+                                .help("Only run benchmarks with this tag, may be repeated")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1),
+                        ),
+                )
+                .setting(AppSettings::SubcommandRequiredElseHelp),
+        )
+        .subcommand(
+            SubCommand::with_name("tui")
+                .about("Dashboard of the server queue, its environment and recent results"),
+        )
+        .subcommand(SubCommand::with_name("env").about("Show the server environment"))
+        .subcommand(SubCommand::with_name("compilers").about("List the server compilers"))
+        .setting(AppSettings::SubcommandRequiredElseHelp)
         .get_matches();
 
     let config_file_path = if matches.value_of("config").is_none() {
@@ -54,5 +313,29 @@ fn main() {
         matches.value_of("config").unwrap()
     };
 
-    info!("Config path: {}", config_file_path)
+    info!("Config path: {}", config_file_path);
+
+    let result = match matches.subcommand() {
+        ("run", Some(matches)) if matches.is_present("local") => command::run_local(matches),
+        (subcommand, Some(matches)) => setup_config(config_file_path, matches.value_of("server"))
+            .and_then(|config| match subcommand {
+                "run" => command::run(&config, matches),
+                "watch" => command::watch(&config, matches),
+                "show" => command::show(&config, matches),
+                "compare" => command::compare(&config, matches),
+                "report" => command::report(&config, matches),
+                "baseline" => command::baseline(&config, matches),
+                "suite" => command::suite(&config, matches),
+                "tui" => tui::run(&config),
+                "env" => command::env(&config, matches),
+                "compilers" => command::compilers(&config, matches),
+                _ => Ok(()),
+            }),
+        _ => Ok(()),
+    };
+
+    if let Err(e) = result {
+        error!("{}", e);
+        process::exit(1);
+    }
 }
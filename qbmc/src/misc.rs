@@ -1 +1,3 @@
 pub const QBM_DEFAULT_CONFIG_FILE_PATH: &str = "qbmc.toml";
+pub const QBM_REGRESSION_EXIT_CODE: i32 = 2;
+pub const QBM_API_BASE: &str = "/api/v1";
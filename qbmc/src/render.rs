@@ -0,0 +1,386 @@
+use crate::format::{compiler_rows, Rows};
+use std::io::{self, Write};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use util::{
+    box_plots, format_ns, histogram, welch_t_test, BenchmarkInfo, CompilerInfo, RemoteServerInfo,
+    TaskRecord,
+};
+
+pub fn write_result(out: &mut dyn Write, result: &BenchmarkInfo) -> io::Result<()> {
+    if !result.message.trim().is_empty() {
+        writeln!(out, "{}", result.message.trim_end())?;
+    }
+
+    if !result.output.is_empty() {
+        writeln!(out, "{}", result.output.trim_end())?;
+    }
+
+    if !result.timings.is_empty() {
+        let min = result.timings.iter().min().copied().unwrap_or_default();
+        let max = result.timings.iter().max().copied().unwrap_or_default();
+        let mean = result.timings.iter().sum::<u64>() / result.timings.len() as u64;
+
+        writeln!(
+            out,
+            "{} iterations, mean {}, min {}, max {}",
+            result.timings.len(),
+            format_ns(mean),
+            format_ns(min),
+            format_ns(max)
+        )?;
+    }
+
+    if let Some(node) = &result.node {
+        writeln!(out, "Ran on node {}", node)?;
+    }
+
+    for warning in &result.warnings {
+        writeln!(out, "warning: {}", warning)?;
+    }
+
+    if let Some(fingerprint) = &result.fingerprint {
+        let setting = |s: Option<bool>| match s {
+            Some(true) => "on",
+            Some(false) => "off",
+            None => "unknown",
+        };
+
+        writeln!(
+            out,
+            "Machine {} ({}): {}, governor {}, turbo {}, SMT {}, load {:.2}, memory {:.0}% used",
+            fingerprint.id(),
+            fingerprint.host_name,
+            fingerprint.cpu_brand,
+            fingerprint.governor.as_deref().unwrap_or("unknown"),
+            setting(fingerprint.turbo),
+            setting(fingerprint.smt),
+            fingerprint.load_average[0],
+            fingerprint.memory_used * 100.0
+        )?;
+    }
+
+    if let Some(comparison) = &result.comparison {
+        let p_value = comparison
+            .p_value
+            .map(|p| format!(", p={:.4}", p))
+            .unwrap_or_default();
+
+        writeln!(
+            out,
+            "{:+.1}% vs baseline {} (mean {}{}){}",
+            comparison.change * 100.0,
+            comparison.baseline_uid,
+            format_ns(comparison.baseline_mean_ns as u64),
+            p_value,
+            if comparison.regression {
+                " REGRESSION"
+            } else {
+                ""
+            }
+        )?;
+
+        for warning in &comparison.warnings {
+            writeln!(out, "warning: baseline {}", warning)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_record(out: &mut dyn Write, record: &TaskRecord) -> io::Result<()> {
+    let created_at = OffsetDateTime::from_unix_timestamp(record.created_at)
+        .ok()
+        .and_then(|t| t.format(&Rfc3339).ok())
+        .unwrap_or_else(|| record.created_at.to_string());
+
+    writeln!(out, "Task:     {}", record.task_uid)?;
+    writeln!(out, "Created:  {}", created_at)?;
+    writeln!(
+        out,
+        "Compiler: {} {}",
+        record.compiler, record.compiler_version
+    )?;
+    writeln!(out, "Flags:    {}", record.compiler_args)?;
+
+    if let Some(name) = &record.name {
+        writeln!(out, "Name:     {}", name)?;
+    }
+
+    if let Some(user) = &record.user {
+        writeln!(out, "User:     {}", user)?;
+    }
+
+    if !record.tags.is_empty() {
+        writeln!(out, "Tags:     {}", record.tags.join(", "))?;
+    }
+
+    if let Some(env) = &record.environment {
+        writeln!(
+            out,
+            "Server:   {} ({} cores / {} threads), {} {}, {} memory",
+            env.cpu_brand.trim(),
+            env.cpu_cores,
+            env.cpu_logical_cores,
+            env.os_version,
+            env.kernel_version,
+            env.total_memory
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "--- {} ---", record.source_file)?;
+    writeln!(out, "{}", record.source_code.trim_end())?;
+    writeln!(out)?;
+    writeln!(out, "--- result ---")?;
+    write_result(out, &record.result)
+}
+
+/// One row per machine keyed by CPU brand, fastest first.
+pub fn write_machine_comparison(
+    out: &mut dyn Write,
+    results: &[(String, Result<BenchmarkInfo, String>)],
+) -> io::Result<()> {
+    let mut rows = vec![];
+    let mut failures = vec![];
+
+    for (server, result) in results {
+        match result {
+            Ok(info) if !info.timings.is_empty() => {
+                let cpu = info
+                    .fingerprint
+                    .as_ref()
+                    .map(|f| f.cpu_brand.clone())
+                    .unwrap_or_else(|| "unknown".to_string());
+                let server = info.node.clone().unwrap_or_else(|| server.clone());
+                let mean = info.timings.iter().sum::<u64>() / info.timings.len() as u64;
+                let min = info.timings.iter().min().copied().unwrap_or_default();
+                let max = info.timings.iter().max().copied().unwrap_or_default();
+
+                rows.push((cpu, server, mean, min, max));
+            }
+            Ok(info) => failures.push((server, info.message.trim().to_string())),
+            Err(e) => failures.push((server, e.clone())),
+        }
+    }
+
+    rows.sort_by_key(|r| r.2);
+
+    let fastest = rows.first().map(|r| r.2).unwrap_or(1).max(1);
+    let mut table = Rows::new(&["CPU", "Server", "Mean", "Min", "Max", "Relative"]);
+
+    for (cpu, server, mean, min, max) in rows {
+        table.push(vec![
+            cpu,
+            server,
+            format_ns(mean),
+            format_ns(min),
+            format_ns(max),
+            format!("{:.2}x", mean as f64 / fastest as f64),
+        ]);
+    }
+
+    table.write_aligned(out)?;
+
+    for (server, message) in failures {
+        writeln!(out, "{} failed: {}", server, message)?;
+    }
+
+    Ok(())
+}
+
+pub fn write_env(out: &mut dyn Write, env: &RemoteServerInfo) -> io::Result<()> {
+    writeln!(out, "Platform: {} {}", env.platform, env.os_version)?;
+    writeln!(out, "Kernel:   {}", env.kernel_version)?;
+    writeln!(
+        out,
+        "CPU:      {} {} ({} cores / {} threads)",
+        env.cpu_vendor,
+        env.cpu_brand.trim(),
+        env.cpu_cores,
+        env.cpu_logical_cores
+    )?;
+    writeln!(
+        out,
+        "Memory:   {}, {} swap",
+        env.total_memory, env.total_swap
+    )?;
+
+    for failure in &env.readiness {
+        writeln!(out, "not ready: {}", failure)?;
+    }
+
+    Ok(())
+}
+
+pub fn write_compilers(out: &mut dyn Write, compilers: &[CompilerInfo]) -> io::Result<()> {
+    compiler_rows(compilers).write_aligned(out)
+}
+
+/// One row per benchmark of a suite, in manifest order.
+pub fn write_suite_summary(
+    out: &mut dyn Write,
+    results: &[(String, Result<BenchmarkInfo, String>)],
+) -> io::Result<()> {
+    let mut table = Rows::new(&["Benchmark", "Mean", "Min", "Max", "Baseline", "Status"]);
+
+    for (name, result) in results {
+        let info = match result {
+            Ok(info) if !info.timings.is_empty() => info,
+            Ok(info) => {
+                table.push(failed_row(name, info.message.trim()));
+                continue;
+            }
+            Err(e) => {
+                table.push(failed_row(name, e));
+                continue;
+            }
+        };
+
+        let mean = info.timings.iter().sum::<u64>() / info.timings.len() as u64;
+        let min = info.timings.iter().min().copied().unwrap_or_default();
+        let max = info.timings.iter().max().copied().unwrap_or_default();
+        let (change, status) = match &info.comparison {
+            Some(c) if c.regression => (format!("{:+.1}%", c.change * 100.0), "REGRESSION"),
+            Some(c) => (format!("{:+.1}%", c.change * 100.0), "ok"),
+            None => ("-".to_string(), "ok"),
+        };
+
+        table.push(vec![
+            name.clone(),
+            format_ns(mean),
+            format_ns(min),
+            format_ns(max),
+            change,
+            status.to_string(),
+        ]);
+    }
+
+    table.write_aligned(out)
+}
+
+fn failed_row(name: &str, message: &str) -> Vec<String> {
+    let message = message.lines().next().unwrap_or_default();
+
+    vec![
+        name.to_string(),
+        "-".to_string(),
+        "-".to_string(),
+        "-".to_string(),
+        "-".to_string(),
+        format!("failed: {}", message),
+    ]
+}
+
+/// One line per `watch` run with its change against the previous good run.
+pub fn write_watch_run(
+    out: &mut dyn Write,
+    run: u32,
+    result: &BenchmarkInfo,
+    previous: Option<(u32, &BenchmarkInfo)>,
+) -> io::Result<()> {
+    if result.timings.is_empty() {
+        writeln!(out, "#{} failed", run)?;
+        return writeln!(out, "{}", result.message.trim_end());
+    }
+
+    let mean = |info: &BenchmarkInfo| info.timings.iter().sum::<u64>() / info.timings.len() as u64;
+    let min = result.timings.iter().min().copied().unwrap_or_default();
+    let max = result.timings.iter().max().copied().unwrap_or_default();
+    let delta = previous
+        .map(|(previous_run, info)| {
+            let change = mean(result) as f64 / mean(info).max(1) as f64 - 1.0;
+            format!(", {:+.1}% vs #{}", change * 100.0, previous_run)
+        })
+        .unwrap_or_default();
+
+    writeln!(
+        out,
+        "#{} mean {}, min {}, max {}{}",
+        run,
+        format_ns(mean(result)),
+        format_ns(min),
+        format_ns(max),
+        delta
+    )
+}
+
+/// What differs between two stored results, `base` is called A.
+pub fn comparison_lines(base: &TaskRecord, other: &TaskRecord) -> Vec<String> {
+    let describe = |label: &str, record: &TaskRecord| {
+        format!(
+            "{} {} {} {} {}: {}",
+            label,
+            short_uid(&record.task_uid),
+            record.name.as_deref().unwrap_or("-"),
+            record.compiler,
+            record.compiler_args,
+            mean_ns(&record.result)
+                .map(format_ns)
+                .unwrap_or_else(|| "failed".to_string())
+        )
+    };
+
+    let mut lines = vec![describe("A", base), describe("B", other)];
+
+    if let (Some(a), Some(b)) = (mean_ns(&base.result), mean_ns(&other.result)) {
+        let samples =
+            |info: &BenchmarkInfo| info.timings.iter().map(|&t| t as f64).collect::<Vec<_>>();
+        let p_value = welch_t_test(&samples(&base.result), &samples(&other.result))
+            .map(|p| format!(", p={:.4}", p))
+            .unwrap_or_default();
+
+        lines.push(format!(
+            "B is {:+.1}% vs A{}",
+            (b as f64 / a.max(1) as f64 - 1.0) * 100.0,
+            p_value
+        ));
+    }
+
+    if let (Some(a), Some(b)) = (&base.result.fingerprint, &other.result.fingerprint) {
+        lines.extend(a.differences(b));
+    }
+
+    lines
+}
+
+/// The comparison of two results followed by their box plots.
+pub fn write_comparison(
+    out: &mut dyn Write,
+    base: &TaskRecord,
+    other: &TaskRecord,
+) -> io::Result<()> {
+    for line in comparison_lines(base, other) {
+        writeln!(out, "{}", line)?;
+    }
+
+    writeln!(out)?;
+
+    for line in box_plots(&[("A", &base.result.timings), ("B", &other.result.timings)]) {
+        writeln!(out, "{}", line)?;
+    }
+
+    Ok(())
+}
+
+pub fn write_histogram(out: &mut dyn Write, timings: &[u64]) -> io::Result<()> {
+    if timings.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+
+    for line in histogram(timings) {
+        writeln!(out, "{}", line)?;
+    }
+
+    Ok(())
+}
+
+pub fn mean_ns(info: &BenchmarkInfo) -> Option<u64> {
+    (!info.timings.is_empty()).then(|| info.timings.iter().sum::<u64>() / info.timings.len() as u64)
+}
+
+pub fn short_uid(task_uid: &str) -> &str {
+    task_uid.get(..8).unwrap_or(task_uid)
+}
@@ -0,0 +1,224 @@
+use crate::conf::ServerProfile;
+use figment::providers::{Format, Toml};
+use figment::Figment;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use util::{BenchmarkTask, NodeConstraints, QBM_PROTOCOL_VERSION};
+
+/// Settings of a benchmark, `[defaults]` fills in what a benchmark leaves
+/// out and the server profile what both leave out.
+#[derive(Deserialize, Default, Clone)]
+pub struct Settings {
+    pub compiler: Option<String>,
+    pub compiler_version: Option<String>,
+    pub args: Option<String>,
+    pub iterations: Option<u32>,
+}
+
+/// A `[[benchmarks]]` entry of a suite manifest.
+#[derive(Deserialize)]
+pub struct Benchmark {
+    /// Also the name its baseline is kept under.
+    pub name: String,
+    /// Source file, relative to the manifest.
+    pub file: PathBuf,
+    #[serde(flatten)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Task uid pinned as the baseline before the suite runs.
+    pub baseline: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Suite {
+    #[serde(default)]
+    pub defaults: Settings,
+    #[serde(default)]
+    pub benchmarks: Vec<Benchmark>,
+}
+
+pub fn load_suite(manifest: &str) -> Result<Suite, String> {
+    if !Path::new(manifest).is_file() {
+        return Err(format!("suite manifest {} not found", manifest));
+    }
+
+    let suite = from_figment(Figment::new().merge(Toml::file(manifest)))
+        .map_err(|e| format!("invalid suite {}, {}", manifest, e))?;
+    let root = Path::new(manifest)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+
+    Ok(Suite {
+        benchmarks: suite
+            .benchmarks
+            .into_iter()
+            .map(|b| Benchmark {
+                file: root.join(&b.file),
+                ..b
+            })
+            .collect(),
+        ..suite
+    })
+}
+
+fn from_figment(figment: Figment) -> Result<Suite, String> {
+    let suite = figment.extract::<Suite>().map_err(|e| e.to_string())?;
+
+    if suite.benchmarks.is_empty() {
+        return Err("no [[benchmarks]]".to_string());
+    }
+
+    let mut names = HashSet::new();
+
+    for benchmark in &suite.benchmarks {
+        if !names.insert(&benchmark.name) {
+            return Err(format!("benchmark {} is listed twice", benchmark.name));
+        }
+    }
+
+    Ok(suite)
+}
+
+impl Suite {
+    /// Benchmarks with any of `tags`, or all of them without tags.
+    pub fn select(&self, tags: &[&str]) -> Vec<&Benchmark> {
+        self.benchmarks
+            .iter()
+            .filter(|b| tags.is_empty() || b.tags.iter().any(|t| tags.contains(&t.as_str())))
+            .collect()
+    }
+}
+
+impl Benchmark {
+    pub fn read_source(&self) -> Result<(String, String), String> {
+        let source_code = fs::read_to_string(&self.file)
+            .map_err(|e| format!("unable to read {}, {}", self.file.display(), e))?;
+        let source_file = self
+            .file
+            .file_name()
+            .and_then(|f| f.to_str())
+            .ok_or_else(|| format!("invalid source file {}", self.file.display()))?;
+
+        Ok((source_file.to_string(), source_code))
+    }
+
+    pub fn task(
+        &self,
+        defaults: &Settings,
+        profile: &ServerProfile,
+        task_uid: &str,
+        source_file: &str,
+        source_code: &str,
+    ) -> Result<BenchmarkTask, String> {
+        let settings = &self.settings;
+        let compiler = settings
+            .compiler
+            .as_ref()
+            .or(defaults.compiler.as_ref())
+            .or(profile.compiler.as_ref())
+            .ok_or_else(|| format!("no compiler for {}", self.name))?;
+        let compiler_version = settings
+            .compiler_version
+            .as_ref()
+            .or(defaults.compiler_version.as_ref())
+            .or(profile.compiler_version.as_ref())
+            .ok_or_else(|| format!("no compiler version for {}", self.name))?;
+        let compiler_args = settings
+            .args
+            .as_ref()
+            .or(defaults.args.as_ref())
+            .or(profile.args.as_ref())
+            .map(|a| a.as_str())
+            .unwrap_or("");
+
+        Ok(BenchmarkTask {
+            protocol_version: QBM_PROTOCOL_VERSION,
+            task_uid: task_uid.into(),
+            compiler: compiler.into(),
+            compiler_version: compiler_version.into(),
+            source_file: source_file.into(),
+            source_code: source_code.into(),
+            compiler_args: compiler_args.into(),
+            assembly: false,
+            iterations: settings.iterations.or(defaults.iterations).unwrap_or(1),
+            name: Some(self.name.clone()),
+            user: None,
+            tags: self.tags.clone(),
+            constraints: NodeConstraints::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUITE: &str = r#"
+        [defaults]
+        compiler = "g++"
+        args = "-O2"
+        iterations = 5
+
+        [[benchmarks]]
+        name = "sort"
+        file = "sort.cpp"
+        tags = ["algo"]
+
+        [[benchmarks]]
+        name = "hash"
+        file = "hash.cpp"
+        args = "-O3"
+        iterations = 10
+        tags = ["algo", "nightly"]
+    "#;
+
+    fn load(toml: &str) -> Result<Suite, String> {
+        from_figment(Figment::from(Toml::string(toml)))
+    }
+
+    #[test]
+    fn merges_defaults() {
+        let suite = load(SUITE).unwrap();
+        let profile = ServerProfile {
+            compiler_version: Some("11.2.0".to_string()),
+            args: Some("-O0".to_string()),
+            ..Default::default()
+        };
+
+        let sort = suite.benchmarks[0]
+            .task(&suite.defaults, &profile, "uid", "sort.cpp", "")
+            .unwrap();
+        assert_eq!(sort.compiler, "g++");
+        assert_eq!(sort.compiler_version, "11.2.0");
+        assert_eq!(sort.compiler_args, "-O2");
+        assert_eq!(sort.iterations, 5);
+        assert_eq!(sort.name.as_deref(), Some("sort"));
+
+        let hash = suite.benchmarks[1]
+            .task(&suite.defaults, &profile, "uid", "hash.cpp", "")
+            .unwrap();
+        assert_eq!(hash.compiler_args, "-O3");
+        assert_eq!(hash.iterations, 10);
+    }
+
+    #[test]
+    fn filters_by_tag() {
+        let suite = load(SUITE).unwrap();
+
+        assert_eq!(suite.select(&[]).len(), 2);
+        assert_eq!(suite.select(&["algo"]).len(), 2);
+        assert_eq!(suite.select(&["nightly"])[0].name, "hash");
+        assert!(suite.select(&["io"]).is_empty());
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let toml = "[[benchmarks]]\nname = 'a'\nfile = 'a.cpp'\n\n[[benchmarks]]\nname = 'a'\nfile = 'b.cpp'\n";
+
+        assert!(load(toml).is_err());
+        assert!(load("[defaults]\ncompiler = 'g++'\n").is_err());
+    }
+}
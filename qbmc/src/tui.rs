@@ -0,0 +1,430 @@
+use crate::client::Client;
+use crate::conf::ClientConfig;
+use crate::render::{comparison_lines, mean_ns, short_uid};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Sparkline, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::OffsetDateTime;
+use util::{
+    format_ns, ActiveTask, BenchmarkInfo, BenchmarkTask, NodeConstraints, RemoteServerInfo,
+    TaskRecord, TaskState, QBM_PROTOCOL_VERSION,
+};
+use uuid::Uuid;
+
+const HISTORY_LIMIT: usize = 50;
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const CREATED_AT: &[FormatItem<'_>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+
+/// What the server looked like at the last refresh.
+#[derive(Default)]
+struct Snapshot {
+    env: Option<RemoteServerInfo>,
+    tasks: Vec<ActiveTask>,
+    history: Vec<TaskRecord>,
+}
+
+enum Update {
+    Refreshed(Result<Snapshot, String>),
+    Submitted(Result<BenchmarkInfo, String>),
+}
+
+struct App {
+    server: String,
+    client: Client,
+    snapshot: Snapshot,
+    table: TableState,
+    /// Result the selected one is compared with.
+    marked: Option<TaskRecord>,
+    comparison: Vec<String>,
+    status: String,
+}
+
+/// Dashboard of the selected server until `q` is pressed.
+pub fn run(config: &ClientConfig) -> Result<(), String> {
+    let client = Client::new(&config.server);
+    client.handshake()?;
+    let (sender, receiver) = mpsc::channel();
+
+    {
+        let client = client.clone();
+        let sender = sender.clone();
+
+        thread::spawn(move || loop {
+            if sender.send(Update::Refreshed(snapshot(&client))).is_err() {
+                break;
+            }
+
+            thread::sleep(REFRESH_INTERVAL);
+        });
+    }
+
+    let mut app = App {
+        server: config.name.clone(),
+        client,
+        snapshot: Snapshot::default(),
+        table: TableState::default().with_selected(Some(0)),
+        marked: None,
+        comparison: vec![],
+        status: "connecting".to_string(),
+    };
+
+    let mut terminal =
+        ratatui::try_init().map_err(|e| format!("unable to open terminal, {}", e))?;
+    let result = event_loop(&mut terminal, &mut app, &sender, &receiver);
+    ratatui::restore();
+
+    result
+}
+
+fn snapshot(client: &Client) -> Result<Snapshot, String> {
+    Ok(Snapshot {
+        env: Some(client.env()?),
+        tasks: client.active_tasks()?,
+        history: client.history(HISTORY_LIMIT)?,
+    })
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    sender: &Sender<Update>,
+    receiver: &Receiver<Update>,
+) -> Result<(), String> {
+    loop {
+        for update in receiver.try_iter() {
+            match update {
+                Update::Refreshed(Ok(snapshot)) => {
+                    if app.status == "connecting" || app.status.starts_with("refresh failed") {
+                        app.status.clear();
+                    }
+                    app.snapshot = snapshot;
+                }
+                Update::Refreshed(Err(e)) => app.status = format!("refresh failed: {}", e),
+                Update::Submitted(Ok(result)) if result.timings.is_empty() => {
+                    app.status = format!(
+                        "{} failed: {}",
+                        short_uid(&result.task_uid),
+                        first_line(&result.message)
+                    );
+                }
+                Update::Submitted(Ok(result)) => {
+                    app.status = format!("{} finished", short_uid(&result.task_uid));
+                }
+                Update::Submitted(Err(e)) => app.status = format!("resubmit failed: {}", e),
+            }
+        }
+
+        // Rendering an empty table clears the selection.
+        let count = app.snapshot.history.len();
+        match app.table.selected() {
+            None if count > 0 => app.table.select(Some(0)),
+            Some(selected) if selected >= count && count > 0 => app.table.select(Some(count - 1)),
+            _ => {}
+        }
+
+        terminal
+            .draw(|frame| draw(frame, app))
+            .map_err(|e| e.to_string())?;
+
+        if !event::poll(Duration::from_millis(100)).map_err(|e| e.to_string())? {
+            continue;
+        }
+
+        let key = match event::read().map_err(|e| e.to_string())? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => app.table.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => app.table.select_previous(),
+            KeyCode::Char('r') => resubmit(app, sender),
+            KeyCode::Char('m') => {
+                app.marked = selected(app).cloned();
+                app.comparison.clear();
+
+                if let Some(marked) = &app.marked {
+                    app.status = format!(
+                        "marked {}, select another result and press c",
+                        short_uid(&marked.task_uid)
+                    );
+                }
+            }
+            KeyCode::Char('c') => match (&app.marked, selected(app)) {
+                (Some(marked), Some(record)) => app.comparison = comparison_lines(marked, record),
+                _ => app.status = "mark a result with m first".to_string(),
+            },
+            _ => {}
+        }
+    }
+}
+
+fn selected(app: &App) -> Option<&TaskRecord> {
+    app.table
+        .selected()
+        .and_then(|i| app.snapshot.history.get(i))
+}
+
+/// Submits the selected result again under a new uid and on the same node,
+/// it shows up in the history once it is done.
+fn resubmit(app: &mut App, sender: &Sender<Update>) {
+    let record = match selected(app) {
+        Some(record) => record.clone(),
+        None => return,
+    };
+    let client = app.client.clone();
+    let sender = sender.clone();
+    let task_uid = Uuid::new_v4().to_string();
+
+    app.status = format!(
+        "resubmitted {} as {}",
+        short_uid(&record.task_uid),
+        short_uid(&task_uid)
+    );
+
+    thread::spawn(move || {
+        let task = BenchmarkTask {
+            protocol_version: QBM_PROTOCOL_VERSION,
+            task_uid,
+            compiler: record.compiler,
+            compiler_version: record.compiler_version,
+            source_file: record.source_file,
+            source_code: record.source_code,
+            compiler_args: record.compiler_args,
+            assembly: record.assembly,
+            iterations: record.iterations,
+            name: record.name,
+            user: record.user,
+            tags: record.tags,
+            // A rerun elsewhere would not be comparable to the original.
+            constraints: NodeConstraints {
+                node: record.result.node,
+                ..Default::default()
+            },
+        };
+
+        let _ = sender.send(Update::Submitted(client.run(&task)));
+    });
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [top, history, details, footer] = Layout::vertical([
+        Constraint::Length(7),
+        Constraint::Min(6),
+        Constraint::Length(7),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [env, queue] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(top);
+
+    draw_env(frame, app, env);
+    draw_queue(frame, app, queue);
+    draw_history(frame, app, history);
+    draw_details(frame, app, details);
+
+    let keys = "q quit  ↑↓ select  r resubmit  m mark  c compare with marked";
+    let footer_text = if app.status.is_empty() {
+        keys.to_string()
+    } else {
+        format!("{}  |  {}", keys, app.status)
+    };
+    frame.render_widget(
+        Paragraph::new(footer_text).style(Style::new().fg(Color::DarkGray)),
+        footer,
+    );
+}
+
+fn draw_env(frame: &mut Frame, app: &App, area: Rect) {
+    let lines = match &app.snapshot.env {
+        Some(env) => {
+            let mut lines = vec![
+                Line::from(format!("{} {}", env.platform, env.os_version)),
+                Line::from(format!("Kernel {}", env.kernel_version)),
+                Line::from(format!(
+                    "{} ({} cores / {} threads)",
+                    env.cpu_brand.trim(),
+                    env.cpu_cores,
+                    env.cpu_logical_cores
+                )),
+                Line::from(format!(
+                    "Memory {}, {} swap",
+                    env.total_memory, env.total_swap
+                )),
+            ];
+
+            lines.extend(env.readiness.iter().map(|r| {
+                Line::styled(format!("not ready: {}", r), Style::new().fg(Color::Yellow))
+            }));
+
+            lines
+        }
+        None => vec![Line::from("-")],
+    };
+
+    let block = Block::bordered().title(format!(" {} ", app.server));
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_queue(frame: &mut Frame, app: &App, area: Rect) {
+    let rows = app.snapshot.tasks.iter().map(|task| {
+        let state = match task.state {
+            TaskState::Queued => "queued".to_string(),
+            TaskState::Compiling => "compiling".to_string(),
+            TaskState::Running if task.iterations > 0 => {
+                format!("running {}/{}", task.iteration, task.iterations)
+            }
+            TaskState::Running => "running".to_string(),
+        };
+
+        Row::new(vec![short_uid(&task.task_uid).to_string(), state])
+    });
+
+    let block = Block::bordered().title(format!(" Queue ({}) ", app.snapshot.tasks.len()));
+    let table = Table::new(rows, [Constraint::Length(8), Constraint::Min(10)]).block(block);
+    frame.render_widget(table, area);
+}
+
+fn draw_history(frame: &mut Frame, app: &mut App, area: Rect) {
+    let marked = app.marked.as_ref().map(|m| m.task_uid.as_str());
+
+    let rows = app.snapshot.history.iter().map(|record| {
+        let created_at = OffsetDateTime::from_unix_timestamp(record.created_at)
+            .ok()
+            .and_then(|t| t.format(CREATED_AT).ok())
+            .unwrap_or_default();
+        let mark = if marked == Some(record.task_uid.as_str()) {
+            "*"
+        } else {
+            ""
+        };
+        let status = match &record.result.comparison {
+            Some(c) if c.regression => "REGRESSION",
+            _ if record.result.timings.is_empty() => "failed",
+            _ => "",
+        };
+
+        Row::new(vec![
+            mark.to_string(),
+            short_uid(&record.task_uid).to_string(),
+            created_at,
+            record.name.clone().unwrap_or_default(),
+            format!("{} {}", record.compiler, record.compiler_version),
+            record.compiler_args.clone(),
+            mean_ns(&record.result).map(format_ns).unwrap_or_default(),
+            sparkline(&record.result.timings, 16),
+            status.to_string(),
+        ])
+    });
+
+    let header = Row::new(vec![
+        "", "Task", "Created", "Name", "Compiler", "Flags", "Mean", "Timings", "",
+    ])
+    .style(Style::new().add_modifier(Modifier::BOLD));
+    let widths = [
+        Constraint::Length(1),
+        Constraint::Length(8),
+        Constraint::Length(19),
+        Constraint::Length(16),
+        Constraint::Length(14),
+        Constraint::Min(8),
+        Constraint::Length(12),
+        Constraint::Length(16),
+        Constraint::Length(10),
+    ];
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::bordered().title(" Recent results "))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, area, &mut app.table);
+}
+
+fn draw_details(frame: &mut Frame, app: &App, area: Rect) {
+    if !app.comparison.is_empty() {
+        let lines = app
+            .comparison
+            .iter()
+            .map(|l| Line::from(l.as_str()))
+            .collect::<Vec<_>>();
+        let block = Block::bordered().title(" Comparison ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+        return;
+    }
+
+    let record = match selected(app) {
+        Some(record) => record,
+        None => {
+            frame.render_widget(Block::bordered().title(" Timings "), area);
+            return;
+        }
+    };
+
+    let title = match &record.result.fingerprint {
+        Some(fingerprint) => format!(
+            " Timings of {} on {} ({}) ",
+            short_uid(&record.task_uid),
+            fingerprint.host_name,
+            fingerprint.cpu_brand
+        ),
+        None => format!(" Timings of {} ", short_uid(&record.task_uid)),
+    };
+
+    if record.result.timings.is_empty() {
+        let paragraph =
+            Paragraph::new(record.result.message.as_str()).block(Block::bordered().title(title));
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let sparkline = Sparkline::default()
+        .block(Block::bordered().title(title))
+        .data(&record.result.timings)
+        .style(Style::new().fg(Color::Cyan));
+    frame.render_widget(sparkline, area);
+}
+
+/// `values` as block characters scaled between their minimum and maximum,
+/// the latest `width` values only.
+fn sparkline(values: &[u64], width: usize) -> String {
+    let values = &values[values.len().saturating_sub(width)..];
+    let min = values.iter().min().copied().unwrap_or_default();
+    let max = values.iter().max().copied().unwrap_or_default();
+    let top = SPARK_CHARS.len() - 1;
+
+    values
+        .iter()
+        .map(|&v| match max - min {
+            0 => SPARK_CHARS[top / 2],
+            range => SPARK_CHARS[((v - min) * top as u64 / range) as usize],
+        })
+        .collect()
+}
+
+fn first_line(message: &str) -> &str {
+    message.lines().next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_sparklines() {
+        assert_eq!(sparkline(&[10, 20, 30], 16), "▁▄█");
+        assert_eq!(sparkline(&[5, 5], 16), "▄▄");
+        assert_eq!(sparkline(&[1, 2, 3, 4], 2), "▁█");
+        assert_eq!(sparkline(&[], 16), "");
+    }
+}
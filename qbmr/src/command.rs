@@ -0,0 +1,120 @@
+use crate::engine::EngineErrorMessage;
+
+const COMPILER: &str = "${COMPILER}";
+const COMPILER_ARGS: &str = "${COMPILER_ARGS}";
+const SOURCE_FILES: &str = "${SOURCE_FILES}";
+const TARGET_FILE_NAME: &str = "${TARGET_FILE_NAME}";
+
+/// A compiler command line template, tokenized once when the engine is built.
+///
+/// Placeholders are expanded per token, `${COMPILER_ARGS}` must be a token of
+/// its own and expands into zero or more separate arguments.
+pub struct CommandTemplate {
+    tokens: Vec<String>,
+}
+
+impl CommandTemplate {
+    pub fn parse(command: &str) -> Result<CommandTemplate, EngineErrorMessage> {
+        let tokens = shell_words::split(command)
+            .map_err(|e| format!("invalid command template {:?}, {}", command, e))?;
+
+        if tokens.is_empty() {
+            return Err("empty command template".to_string());
+        }
+
+        if tokens
+            .iter()
+            .any(|t| t.contains(COMPILER_ARGS) && t != COMPILER_ARGS)
+        {
+            return Err(format!(
+                "{} must be a separate word in {:?}",
+                COMPILER_ARGS, command
+            ));
+        }
+
+        for placeholder in [COMPILER, SOURCE_FILES, TARGET_FILE_NAME] {
+            if !tokens.iter().any(|t| t.contains(placeholder)) {
+                return Err(format!(
+                    "command template {:?} does not use {}",
+                    command, placeholder
+                ));
+            }
+        }
+
+        Ok(CommandTemplate { tokens })
+    }
+
+    pub fn expand(
+        &self,
+        compiler: &str,
+        compiler_args: &[String],
+        source_file: &str,
+        target_file: &str,
+    ) -> Vec<String> {
+        let mut argv = Vec::with_capacity(self.tokens.len() + compiler_args.len());
+
+        for token in &self.tokens {
+            if token == COMPILER_ARGS {
+                argv.extend(compiler_args.iter().cloned());
+            } else {
+                argv.push(
+                    token
+                        .replace(COMPILER, compiler)
+                        .replace(SOURCE_FILES, source_file)
+                        .replace(TARGET_FILE_NAME, target_file),
+                );
+            }
+        }
+
+        argv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_placeholders_into_separate_args() {
+        let template = CommandTemplate::parse(
+            "${COMPILER} ${COMPILER_ARGS} ${SOURCE_FILES}  -o ${TARGET_FILE_NAME}",
+        )
+        .unwrap();
+
+        let argv = template.expand(
+            "/usr/bin/c++",
+            &["-O2".to_string(), "-DX=1; rm -rf ~".to_string()],
+            "/cache/a b.cpp",
+            "/build/a b",
+        );
+
+        assert_eq!(
+            argv,
+            vec![
+                "/usr/bin/c++",
+                "-O2",
+                "-DX=1; rm -rf ~",
+                "/cache/a b.cpp",
+                "-o",
+                "/build/a b"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_embedded_compiler_args() {
+        assert!(CommandTemplate::parse("${COMPILER} -x${COMPILER_ARGS}").is_err());
+        assert!(CommandTemplate::parse("").is_err());
+        assert!(CommandTemplate::parse("${COMPILER} '").is_err());
+    }
+
+    #[test]
+    fn requires_placeholders() {
+        let error = CommandTemplate::parse("${COMPILER} ${SOURCE_FILES} -o a.out")
+            .err()
+            .unwrap();
+
+        assert!(error.contains("${TARGET_FILE_NAME}"));
+        assert!(CommandTemplate::parse("g++ ${SOURCE_FILES} -o ${TARGET_FILE_NAME}").is_err());
+    }
+}
@@ -1,26 +1,254 @@
+use crate::command::CommandTemplate;
+use crate::engine::DEFAULT_ITERATION_TIMEOUT;
 use figment::providers::{Env, Format, Toml};
-use figment::value::{Dict, Map};
 use figment::{Error, Figment};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::HashSet;
+use util::{check_executable, ArgPolicy};
 
 #[derive(Deserialize)]
 pub struct CompilerConfig {
-    pub(crate) name: String,
-    pub(crate) path: String,
-    pub(crate) version: String,
-    pub(crate) command: String,
-    pub(crate) extensions: Vec<String>,
+    pub name: String,
+    pub path: String,
+    pub version: String,
+    pub command: String,
+    pub extensions: Vec<String>,
+}
+
+impl CompilerConfig {
+    /// Problems that would otherwise only show up when a task runs, `key` is
+    /// where the entry is in the config, e.g. `compilers[0]`.
+    pub fn validate(&self, key: &str) -> Vec<String> {
+        let mut errors = vec![];
+
+        if self.name.trim().is_empty() {
+            errors.push(format!("{}.name: must not be empty", key));
+        }
+
+        if self.version.trim().is_empty() {
+            errors.push(format!("{}.version: must not be empty", key));
+        }
+
+        if let Err(e) = check_executable(&self.path) {
+            errors.push(format!("{}.path: {}", key, e));
+        }
+
+        if let Err(e) = CommandTemplate::parse(&self.command) {
+            errors.push(format!("{}.command: {}", key, e));
+        }
+
+        if self.extensions.is_empty() {
+            errors.push(format!(
+                "{}.extensions: must list at least one file extension, e.g. [\"cpp\"]",
+                key
+            ));
+        }
+
+        for (i, extension) in self.extensions.iter().enumerate() {
+            if let Err(e) = validate_extension(extension) {
+                errors.push(format!("{}.extensions[{}]: {}", key, i, e));
+            }
+        }
+
+        errors
+    }
+}
+
+/// Extensions are compared with `Path::extension`, so they have no dot.
+fn validate_extension(extension: &str) -> Result<(), String> {
+    if let Some(stripped) = extension.strip_prefix('.') {
+        return Err(format!("write {:?} instead of {:?}", stripped, extension));
+    }
+
+    let is_valid = !extension.is_empty()
+        && extension
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'_' || b == b'-');
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(format!("invalid file extension {:?}", extension))
+    }
 }
 
 #[derive(Deserialize)]
 pub struct Config {
-    pub(crate) compilers: Vec<CompilerConfig>,
+    pub compilers: Vec<CompilerConfig>,
+    #[serde(default)]
+    pub compiler_args: ArgPolicy,
+    /// Runs of a program taking longer are killed.
+    #[serde(default = "default_iteration_timeout_secs")]
+    pub iteration_timeout_secs: u64,
+}
+
+pub fn default_iteration_timeout_secs() -> u64 {
+    DEFAULT_ITERATION_TIMEOUT.as_secs()
 }
 
-pub fn setup_config() -> Result<Config, Error> {
-    Figment::new()
+/// Problems of a `compilers` list, e.g. the same compiler twice, each
+/// prefixed with `compilers[i]`.
+pub fn validate_compilers(compilers: &[CompilerConfig]) -> Vec<String> {
+    let mut errors = vec![];
+    let mut seen = HashSet::new();
+
+    for (i, compiler) in compilers.iter().enumerate() {
+        let key = format!("compilers[{}]", i);
+
+        errors.extend(compiler.validate(&key));
+
+        if !seen.insert((&compiler.name, &compiler.version)) {
+            errors.push(format!(
+                "{}: {} {} is configured more than once",
+                key, compiler.name, compiler.version
+            ));
+        }
+    }
+
+    errors
+}
+
+impl Config {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = validate_compilers(&self.compilers);
+
+        if self.iteration_timeout_secs == 0 {
+            errors.push("iteration_timeout_secs: must be at least 1".to_string());
+        }
+
+        errors
+    }
+}
+
+/// Every error figment found, each prefixed with the key it is about.
+pub fn config_errors(error: Error) -> Vec<String> {
+    error
+        .into_iter()
+        .map(|e| {
+            let source = e
+                .metadata
+                .as_ref()
+                .and_then(|m| m.source.as_ref())
+                .map(|s| format!(" (in {})", s))
+                .unwrap_or_default();
+
+            match config_key(&e.path) {
+                Some(key) => format!("{}: {}{}", key, e.kind, source),
+                None => format!("{}{}", e.kind, source),
+            }
+        })
+        .collect()
+}
+
+/// `["compilers", "0", "path"]` as `compilers[0].path`.
+fn config_key(path: &[String]) -> Option<String> {
+    let mut key = String::new();
+
+    for segment in path {
+        if segment.parse::<usize>().is_ok() {
+            key.push_str(&format!("[{}]", segment));
+        } else {
+            if !key.is_empty() {
+                key.push('.');
+            }
+
+            key.push_str(segment);
+        }
+    }
+
+    (!key.is_empty()).then_some(key)
+}
+
+pub fn setup_config() -> Result<Config, Vec<String>> {
+    let config = Figment::new()
         .merge(Toml::file("qbmr.toml"))
         .merge(Env::prefixed("QMBR_"))
-        .extract()
+        .extract::<Config>()
+        .map_err(config_errors)?;
+
+    match config.validate() {
+        errors if errors.is_empty() => Ok(config),
+        errors => Err(errors),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(toml: &str) -> Result<Config, Vec<String>> {
+        Figment::new()
+            .merge(Toml::string(toml))
+            .extract::<Config>()
+            .map_err(config_errors)
+    }
+
+    #[test]
+    fn points_to_offending_keys() {
+        let errors = extract(
+            r#"
+            [[compilers]]
+            name = "g++"
+            path = "/usr/bin/g++"
+            version = "11.2.0"
+            extensions = ["cpp"]
+            "#,
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("compilers[0]: "), "{}", errors[0]);
+        assert!(errors[0].contains("command"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn validates_compilers() {
+        let config = extract(
+            r#"
+            [[compilers]]
+            name = "g++"
+            path = "/qbm/no/such/g++"
+            version = "11.2.0"
+            command = "${COMPILER} ${SOURCE_FILES} -o a.out"
+            extensions = [".cpp", "c"]
+
+            [[compilers]]
+            name = "g++"
+            path = "/qbm/no/such/g++"
+            version = "11.2.0"
+            command = "${COMPILER} ${SOURCE_FILES} -o ${TARGET_FILE_NAME}"
+            extensions = []
+            "#,
+        )
+        .unwrap();
+
+        let errors = config.validate();
+
+        assert!(errors
+            .iter()
+            .any(|e| e.starts_with("compilers[0].path: ") && e.contains("does not exist")));
+        assert!(errors
+            .iter()
+            .any(|e| e.starts_with("compilers[0].command: ") && e.contains("${TARGET_FILE_NAME}")));
+        assert!(errors
+            .iter()
+            .any(|e| e.starts_with("compilers[0].extensions[0]: ") && e.contains("\"cpp\"")));
+        assert!(errors
+            .iter()
+            .any(|e| e.starts_with("compilers[1].extensions: ")));
+        assert!(errors
+            .iter()
+            .any(|e| e.starts_with("compilers[1]: ") && e.contains("more than once")));
+        assert!(!errors.iter().any(|e| e.contains("extensions[1]")));
+    }
+
+    #[test]
+    fn example_config_only_needs_the_compilers_in_path() {
+        let config = extract(include_str!("../qbmr.toml")).unwrap();
+
+        for error in config.validate() {
+            assert!(error.ends_with("is not in PATH"), "{}", error);
+        }
+    }
 }
@@ -0,0 +1,134 @@
+use crate::conf::CompilerConfig;
+use regex::Regex;
+use std::ops::Not;
+use std::process::Command;
+use util::CompilerInfo;
+
+/// Template the compilers found on the `PATH` are run with.
+pub const SYSTEM_COMPILER_COMMAND: &str =
+    "${COMPILER} ${COMPILER_ARGS} ${SOURCE_FILES} -o ${TARGET_FILE_NAME}";
+pub const SYSTEM_COMPILER_EXTENSIONS: [&str; 4] = ["cpp", "cc", "cxx", "c"];
+
+/// A compiler found on the `PATH` of this machine.
+pub struct SystemCompiler {
+    pub info: CompilerInfo,
+    pub path: String,
+}
+
+impl SystemCompiler {
+    pub fn config(&self) -> CompilerConfig {
+        CompilerConfig {
+            name: self.info.name.clone(),
+            path: self.path.clone(),
+            version: self.info.version.clone(),
+            command: SYSTEM_COMPILER_COMMAND.to_string(),
+            extensions: SYSTEM_COMPILER_EXTENSIONS
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// `clang++` and `g++` if they are installed.
+pub fn system_compiler_info() -> Vec<CompilerInfo> {
+    vec![compiler_check_system_clang(), compiler_check_system_gcc()]
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Like `system_compiler_info`, plus where each compiler is installed.
+pub fn system_compilers() -> Vec<SystemCompiler> {
+    system_compiler_info()
+        .into_iter()
+        .filter_map(|info| {
+            let path = compiler_path(&info.name)?;
+
+            Some(SystemCompiler { info, path })
+        })
+        .collect()
+}
+
+fn compiler_path(name: &str) -> Option<String> {
+    let output = if cfg!(target_os = "windows") {
+        Command::new("powershell")
+            .args(["-Command", &format!("& {{(gcm {}).Source}}", name)])
+            .output()
+    } else {
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("which {}", name))
+            .output()
+    };
+
+    let output = output.ok()?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    if output.status.success().not() || path.is_empty() {
+        return None;
+    }
+
+    Some(path)
+}
+
+fn compiler_check_system_clang() -> Option<CompilerInfo> {
+    let output = if cfg!(target_os = "windows") {
+        Command::new("cmd")
+            .args(["/C", "clang++ --version"])
+            .output()
+    } else {
+        Command::new("sh")
+            .arg("-c")
+            .arg("clang++ --version")
+            .output()
+    };
+
+    let output = output.ok()?;
+
+    if output.status.success().not() {
+        return None;
+    }
+
+    let clang_output = String::from_utf8_lossy(&output.stdout);
+    let clang_version_regex = Regex::new(r"clang version (?P<version>\d+\.\d+\.\d+\w*)\n").unwrap();
+    let clang_version = &clang_version_regex.captures(&clang_output)?["version"];
+    let clang_target_regex = Regex::new(r"Target: (?P<target>[-\w]+)\n").unwrap();
+    let clang_target = &clang_target_regex.captures(&clang_output)?["target"];
+    let clang_thread_model_regex = Regex::new(r"Thread model: (?P<thread_model>\w+)\n").unwrap();
+    let clang_thread_model = &clang_thread_model_regex.captures(&clang_output)?["thread_model"];
+
+    Some(CompilerInfo {
+        name: "clang++".to_string(),
+        version: clang_version.to_string(),
+        target: Some(clang_target.to_string()),
+        thread_model: Some(clang_thread_model.to_string()),
+    })
+}
+
+fn compiler_check_system_gcc() -> Option<CompilerInfo> {
+    let output = if cfg!(target_os = "windows") {
+        Command::new("cmd").args(["/C", "g++ --version"]).output()
+    } else {
+        Command::new("sh").arg("-c").arg("g++ --version").output()
+    };
+
+    let output = output.ok()?;
+
+    if output.status.success().not() {
+        return None;
+    }
+
+    let gcc_output = String::from_utf8_lossy(&output.stdout);
+    let gcc_regex = Regex::new(r"[\w\.\+]+ \((?P<target>.+)\) (?P<version>\d+\.\d+\.\d+)").unwrap();
+    let gcc_captures = gcc_regex.captures(&gcc_output)?;
+    let gcc_version = &gcc_captures["version"];
+    let gcc_target = &gcc_captures["target"];
+
+    Some(CompilerInfo {
+        name: "g++".to_string(),
+        version: gcc_version.to_string(),
+        target: Some(gcc_target.to_string()),
+        thread_model: None,
+    })
+}
@@ -1,21 +1,109 @@
+use crate::command::CommandTemplate;
 use crate::conf::CompilerConfig;
+use crate::host::{collect_fingerprint, collect_host_state, probe_compiler, CompilerIdentity};
+use crate::platform::{self, Platform};
+use crate::readiness::{ReadinessMode, ReadinessPolicy};
 use path_absolutize::Absolutize;
+use shared_child::SharedChild;
 use std::collections::HashSet;
 use std::fs;
-use std::fs::File;
+use std::io::{self, Read};
 use std::path::Path;
-use std::process::Command;
+use std::process::ExitStatus;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+use util::{
+    create_task_workspace, split_args, validate_file_name, workspace_file, write_workspace_file,
+    ArgPolicy, BenchmarkInfo, BenchmarkTask, TaskWorkspace,
+};
 
 struct EngineCompiler {
     name: String,
     path: String,
-    command: String,
+    command: CommandTemplate,
     extensions: HashSet<String>,
 }
 
 pub type EngineErrorMessage = String;
 
+pub const TASK_CANCELLED: &str = "task cancelled";
+
+/// How long a single run of a program may take unless configured otherwise.
+pub const DEFAULT_ITERATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Stdout and stderr kept of a single run, the rest is read and dropped.
+const MAX_CAPTURED_OUTPUT: usize = 1024 * 1024;
+
+/// A program `Engine::benchmark` started, it can be killed from any thread.
+#[derive(Clone)]
+pub struct RunningProgram(Arc<SharedChild>);
+
+impl RunningProgram {
+    /// Kills the program unless it exited already.
+    pub fn kill(&self) {
+        let _ = self.0.kill();
+    }
+}
+
+/// Progress of `Engine::benchmark`, every method does nothing by default.
+pub trait BenchmarkObserver {
+    fn compiling(&mut self) {}
+
+    fn compiled(&mut self, _output: &str) {}
+
+    /// Called when a run starts, killing `program` ends it early.
+    fn started(&mut self, _iteration: u32, _program: &RunningProgram) {}
+
+    /// Called after every run with its wall clock time in nanoseconds.
+    fn iteration(&mut self, _iteration: u32, _elapsed_ns: u64) {}
+
+    /// Stdout of the program in `iteration`, called while it arrives.
+    fn output(&mut self, _iteration: u32, _output: &str) {}
+
+    /// Checked before and after compiling and after every run, the task
+    /// stops with `TASK_CANCELLED` once it returns `true`.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// A closure is told about every finished iteration.
+impl<F: FnMut(u32, u64)> BenchmarkObserver for F {
+    fn iteration(&mut self, iteration: u32, elapsed_ns: u64) {
+        self(iteration, elapsed_ns)
+    }
+}
+
+/// What a program run by `Engine::run_program` did.
+struct ProgramRun {
+    status: ExitStatus,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    elapsed_ns: u64,
+}
+
+fn truncation_marker(dropped: u64) -> String {
+    format!("\n[{} more bytes of output dropped]\n", dropped)
+}
+
+enum RunEvent {
+    Stdout(Vec<u8>),
+    StdoutClosed,
+    Stderr(Vec<u8>),
+    Exited(io::Result<ExitStatus>, u64),
+}
+
+/// Files of one run, its workspaces are removed when it is dropped.
+struct ExecutionEnv<'a> {
+    compiler: &'a EngineCompiler,
+    source_file_path: String,
+    target_file_path: String,
+    _workspaces: (TaskWorkspace, TaskWorkspace),
+}
+
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum EngineResult {
     EngineExecutionSetupError(EngineErrorMessage),
     EngineCompileError(EngineErrorMessage),
@@ -25,229 +113,617 @@ pub enum EngineResult {
 
 pub struct Engine {
     compilers: Vec<EngineCompiler>,
+    arg_policy: ArgPolicy,
+    readiness: ReadinessPolicy,
+    iteration_timeout: Duration,
+    platform: Box<dyn Platform>,
     source_directory: String,
     build_directory: String,
 }
 
 impl Engine {
     pub fn new(
-        compilers: &Vec<CompilerConfig>,
+        compilers: &[CompilerConfig],
+        arg_policy: &ArgPolicy,
+        source_directory: &str,
+        build_directory: &str,
+    ) -> Result<Engine, EngineErrorMessage> {
+        Self::with_platform(
+            compilers,
+            arg_policy,
+            platform::native(),
+            source_directory,
+            build_directory,
+        )
+    }
+
+    /// Like `new`, but compiles and runs through `platform` instead of the
+    /// one qbmr was built for.
+    pub fn with_platform(
+        compilers: &[CompilerConfig],
+        arg_policy: &ArgPolicy,
+        platform: Box<dyn Platform>,
         source_directory: &str,
         build_directory: &str,
     ) -> Result<Engine, EngineErrorMessage> {
         let source_directory_path = Path::new(source_directory);
 
-        if !source_directory_path.exists() {
-            if fs::create_dir_all(source_directory_path).is_err() {
-                return Err("unable to create source directory.".to_string());
-            }
+        if !source_directory_path.exists() && fs::create_dir_all(source_directory_path).is_err() {
+            return Err("unable to create source directory.".to_string());
         }
 
         let build_directory_path = Path::new(build_directory);
 
-        if !build_directory_path.exists() {
-            if fs::create_dir_all(build_directory_path).is_err() {
-                return Err("unable to create build directory.".to_string());
-            }
+        if !build_directory_path.exists() && fs::create_dir_all(build_directory_path).is_err() {
+            return Err("unable to create build directory.".to_string());
         }
 
         let compilers = compilers
-            .as_slice()
-            .into_iter()
-            .map(|c| EngineCompiler {
-                name: format!("{}-{}", c.name, c.version),
-                path: c.path.clone(),
-                command: c.command.clone(),
-                extensions: HashSet::from_iter(
-                    c.extensions.as_slice().into_iter().map(|e| e.to_string()),
-                ),
+            .iter()
+            .map(|c| {
+                Ok(EngineCompiler {
+                    name: format!("{}-{}", c.name, c.version),
+                    path: c.path.clone(),
+                    command: CommandTemplate::parse(&c.command)?,
+                    extensions: c.extensions.iter().map(|e| e.to_string()).collect(),
+                })
             })
-            .collect();
+            .collect::<Result<_, EngineErrorMessage>>()?;
 
         Ok(Engine {
             compilers,
+            arg_policy: arg_policy.clone(),
+            readiness: ReadinessPolicy::default(),
+            iteration_timeout: DEFAULT_ITERATION_TIMEOUT,
+            platform,
             source_directory: source_directory.to_string(),
             build_directory: build_directory.to_string(),
         })
     }
 
+    /// Checks hosts against `readiness` before `benchmark` runs a task.
+    pub fn with_readiness(mut self, readiness: ReadinessPolicy) -> Engine {
+        self.readiness = readiness;
+        self
+    }
+
+    /// Programs running longer than `timeout` are killed and the task fails.
+    pub fn with_iteration_timeout(mut self, timeout: Duration) -> Engine {
+        self.iteration_timeout = timeout;
+        self
+    }
+
     pub fn execute(
         &self,
+        task_uid: &str,
         compiler: &str,
         compiler_args: &str,
         source_code: &str,
         execution_args: &str,
         file_name: &str,
     ) -> EngineResult {
-        let env_setup_result = self.setup_execution_env(compiler, source_code, file_name);
+        let compiler_args = match split_args(compiler_args)
+            .and_then(|args| self.arg_policy.check(&args).map(|_| args))
+        {
+            Ok(args) => args,
+            Err(message) => return EngineResult::EngineExecutionSetupError(message),
+        };
 
-        if env_setup_result.is_err() {
-            return EngineResult::EngineExecutionSetupError(
-                "unable to setup execution env".to_string(),
-            );
+        let execution_args = match split_args(execution_args) {
+            Ok(args) => args,
+            Err(message) => return EngineResult::EngineExecutionSetupError(message),
+        };
+
+        let env = match self.setup_execution_env(task_uid, compiler, source_code, file_name) {
+            Ok(env) => env,
+            Err(message) => return EngineResult::EngineExecutionSetupError(message),
+        };
+
+        let target_file_path = match self.do_compile(
+            &compiler_args,
+            env.compiler,
+            &env.source_file_path,
+            &env.target_file_path,
+        ) {
+            Ok(_) => &env.target_file_path,
+            Err(message) => return EngineResult::EngineCompileError(message),
+        };
+
+        match self.execute_program(&execution_args, target_file_path) {
+            Ok(output) => EngineResult::EngineExecutionOk(output),
+            Err(message) => EngineResult::EngineExecutionError(message),
         }
+    }
 
-        let (compiler, source_file_path, target_file_path) = env_setup_result.unwrap();
+    /// Compiles `task` and runs it `task.iterations` times, telling
+    /// `observer` about the progress. The host is checked against the
+    /// readiness policy after compiling.
+    pub fn benchmark<O: BenchmarkObserver>(
+        &self,
+        task: &BenchmarkTask,
+        mut observer: O,
+    ) -> BenchmarkInfo {
+        let mut result = BenchmarkInfo::new(&task.task_uid);
+
+        if observer.is_cancelled() {
+            result.message = TASK_CANCELLED.to_string();
+            return result;
+        }
 
-        let compiler_result =
-            Self::do_compile(compiler_args, compiler, source_file_path, &target_file_path);
+        let compiler_args = match split_args(&task.compiler_args)
+            .and_then(|args| self.arg_policy.check(&args).map(|_| args))
+        {
+            Ok(args) => args,
+            Err(message) => {
+                result.message = message;
+                return result;
+            }
+        };
 
-        return if compiler_result.is_ok() {
-            let execution_result = Self::execute_program(execution_args, target_file_path);
+        let compiler_name = format!("{}-{}", task.compiler, task.compiler_version);
+        let env = match self.setup_execution_env(
+            &task.task_uid,
+            &compiler_name,
+            &task.source_code,
+            &task.source_file,
+        ) {
+            Ok(env) => env,
+            Err(message) => {
+                result.message = message;
+                return result;
+            }
+        };
 
-            if execution_result.is_err() {
-                EngineResult::EngineExecutionError(execution_result.err().unwrap())
-            } else {
-                EngineResult::EngineExecutionOk(execution_result.unwrap())
+        observer.compiling();
+
+        match self.run_compiler(
+            &compiler_args,
+            env.compiler,
+            &env.source_file_path,
+            &env.target_file_path,
+        ) {
+            Ok((stdout, stderr)) => {
+                result.message = stdout + &stderr;
+                observer.compiled(&result.message);
+            }
+            Err(message) => {
+                result.message = message;
+                return result;
+            }
+        }
+
+        if observer.is_cancelled() {
+            result.message = TASK_CANCELLED.to_string();
+            return result;
+        }
+
+        let compiler = probe_compiler(&env.compiler.path).unwrap_or_else(|| {
+            result.warnings.push(format!(
+                "unable to identify compiler {}, the fingerprint uses its configured version",
+                env.compiler.path
+            ));
+
+            CompilerIdentity {
+                name: task.compiler.clone(),
+                version: task.compiler_version.clone(),
+            }
+        });
+
+        let host = collect_host_state();
+        result.fingerprint = Some(collect_fingerprint(&compiler, &host));
+
+        // Only the host's readiness can refuse a run, not other warnings.
+        let not_ready = self.readiness.check(&host);
+        let refused = self.readiness.mode == ReadinessMode::Enforce && !not_ready.is_empty();
+
+        if refused {
+            result.message = format!(
+                "host is not ready for benchmarking: {}",
+                not_ready.join(", ")
+            );
+        }
+
+        result.warnings.extend(not_ready);
+
+        if refused {
+            return result;
+        }
+
+        let target_file_path = Path::new(&env.target_file_path);
+        let executable_file_path = self.platform.executable_file(target_file_path);
+        let working_directory = target_file_path.parent().unwrap_or_else(|| Path::new("."));
+        let argv = match Self::absolute_path(executable_file_path) {
+            Ok(executable) => vec![executable],
+            Err(message) => {
+                result.message = message;
+                return result;
             }
-        } else {
-            EngineResult::EngineCompileError(compiler_result.err().unwrap())
         };
+
+        for iteration in 1..=task.iterations {
+            let execution = self.run_program(&argv, working_directory, iteration, &mut observer);
+
+            // A cancelled program is usually killed, its exit status is moot.
+            if observer.is_cancelled() {
+                result.message = TASK_CANCELLED.to_string();
+                return result;
+            }
+
+            let execution = match execution {
+                Ok(execution) if execution.status.success() => execution,
+                Ok(execution) => {
+                    result.message = format!(
+                        "program failed in iteration {}, {}\n{}",
+                        iteration,
+                        self.platform.describe_status(&execution.status),
+                        String::from_utf8_lossy(&execution.stderr)
+                    );
+                    return result;
+                }
+                Err(message) => {
+                    result.message = format!("{} in iteration {}", message, iteration);
+                    return result;
+                }
+            };
+
+            result.output = String::from_utf8_lossy(&execution.stdout).into_owned();
+            result.timings.push(execution.elapsed_ns);
+            observer.iteration(iteration, execution.elapsed_ns);
+        }
+
+        result
+    }
+
+    /// Runs `argv` as `iteration` until it exits or the iteration timeout
+    /// passes, in which case it is killed. `observer` gets the program and its
+    /// stdout while it arrives, the elapsed time is taken as soon as the
+    /// program exits.
+    fn run_program<O: BenchmarkObserver>(
+        &self,
+        argv: &[String],
+        working_directory: &Path,
+        iteration: u32,
+        observer: &mut O,
+    ) -> Result<ProgramRun, EngineErrorMessage> {
+        let start = Instant::now();
+        let deadline = start + self.iteration_timeout;
+
+        let child = self
+            .platform
+            .spawn(argv, working_directory)
+            .and_then(SharedChild::new)
+            .map_err(|e| format!("unable to run program, {}", e))?;
+        let child = Arc::new(child);
+        observer.started(iteration, &RunningProgram(child.clone()));
+
+        let (sender, receiver) = mpsc::channel();
+
+        let stdout = child.take_stdout();
+        let stdout_sender = sender.clone();
+        thread::spawn(move || {
+            if let Some(mut stdout) = stdout {
+                let mut buffer = [0; 8192];
+
+                while let Ok(n @ 1..) = stdout.read(&mut buffer) {
+                    if stdout_sender
+                        .send(RunEvent::Stdout(buffer[..n].to_vec()))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+
+            let _ = stdout_sender.send(RunEvent::StdoutClosed);
+        });
+
+        let stderr = child.take_stderr();
+        let stderr_sender = sender.clone();
+        thread::spawn(move || {
+            let mut bytes = vec![];
+
+            if let Some(mut stderr) = stderr {
+                let _ = (&mut stderr)
+                    .take(MAX_CAPTURED_OUTPUT as u64)
+                    .read_to_end(&mut bytes);
+
+                if let Ok(dropped @ 1..) = io::copy(&mut stderr, &mut io::sink()) {
+                    bytes.extend_from_slice(truncation_marker(dropped).as_bytes());
+                }
+            }
+
+            let _ = stderr_sender.send(RunEvent::Stderr(bytes));
+        });
+
+        let waiter = child.clone();
+        thread::spawn(move || {
+            let status = waiter.wait();
+            let elapsed_ns = start.elapsed().as_nanos() as u64;
+            let _ = sender.send(RunEvent::Exited(status, elapsed_ns));
+        });
+
+        let mut stdout = vec![];
+        let mut stdout_dropped = 0;
+        let mut stdout_open = true;
+        let mut stderr = None;
+        let mut exited = None;
+
+        while stdout_open || stderr.is_none() || exited.is_none() {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(RunEvent::Stdout(chunk)) => {
+                    let kept = chunk.len().min(MAX_CAPTURED_OUTPUT - stdout.len());
+
+                    if kept > 0 {
+                        observer.output(iteration, &String::from_utf8_lossy(&chunk[..kept]));
+                        stdout.extend_from_slice(&chunk[..kept]);
+                    }
+
+                    stdout_dropped += (chunk.len() - kept) as u64;
+                }
+                Ok(RunEvent::StdoutClosed) => stdout_open = false,
+                Ok(RunEvent::Stderr(bytes)) => stderr = Some(bytes),
+                Ok(RunEvent::Exited(status, elapsed_ns)) => exited = Some((status, elapsed_ns)),
+                // Something the program started still holds its output open.
+                Err(_) if exited.is_some() => break,
+                Err(_) => {
+                    let _ = child.kill();
+                    return Err(format!(
+                        "program timed out after {:?}",
+                        self.iteration_timeout
+                    ));
+                }
+            }
+        }
+
+        if stdout_dropped > 0 {
+            let marker = truncation_marker(stdout_dropped);
+            observer.output(iteration, &marker);
+            stdout.extend_from_slice(marker.as_bytes());
+        }
+
+        let (status, elapsed_ns) = exited.expect("the loop only ends once the program exited");
+        let status = status.map_err(|e| format!("unable to run program, {}", e))?;
+
+        Ok(ProgramRun {
+            status,
+            stdout,
+            stderr: stderr.unwrap_or_default(),
+            elapsed_ns,
+        })
     }
 
     fn execute_program(
-        execution_args: &str,
-        target_file_path: String,
+        &self,
+        execution_args: &[String],
+        target_file_path: &str,
     ) -> Result<String, EngineErrorMessage> {
-        let execution_output = Command::new(
-            Path::new(target_file_path.as_str())
-                .absolutize()
-                .unwrap()
-                .to_str()
-                .unwrap(),
-        )
-        .arg(execution_args)
-        .output();
-
-        if execution_output.is_err() {
-            Err(execution_output.err().unwrap().to_string())
+        let target_file_path = Path::new(target_file_path);
+        let executable_file_path = self.platform.executable_file(target_file_path);
+        let working_directory = target_file_path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut argv = vec![Self::absolute_path(executable_file_path)?];
+        argv.extend(execution_args.iter().cloned());
+
+        let execution_output =
+            self.run_program(&argv, working_directory, 1, &mut |_: u32, _: u64| {})?;
+
+        let execution_stderr = String::from_utf8_lossy(&execution_output.stderr).into_owned();
+        let execution_stdout = String::from_utf8_lossy(&execution_output.stdout).into_owned();
+
+        if !execution_output.status.success() {
+            Err(format!(
+                "execute program failed, {}\n{}",
+                self.platform.describe_status(&execution_output.status),
+                execution_stderr
+            ))
+        } else if execution_stdout.is_empty() && execution_stderr.is_empty() {
+            Ok("".to_string())
+        } else if execution_stderr.is_empty() {
+            Ok(execution_stdout)
         } else {
-            let execution_output = execution_output.unwrap();
-            let execution_stderr = String::from_utf8(execution_output.stderr).unwrap();
-            let execution_stdout = String::from_utf8(execution_output.stdout).unwrap();
-
-            if !execution_output.status.success() {
-                Err(format!(
-                    "execute program failed, code: {}\n{}",
-                    execution_output.status, execution_stderr
-                ))
-            } else if execution_stdout.is_empty() && execution_stderr.is_empty() {
-                Ok("".to_string())
-            } else if execution_stderr.is_empty() {
-                Ok(execution_stdout)
-            } else {
-                Err(execution_stderr)
-            }
+            Err(execution_stderr)
         }
     }
 
     fn do_compile(
-        compiler_args: &str,
+        &self,
+        compiler_args: &[String],
         compiler: &EngineCompiler,
-        source_file_path: String,
-        target_file_path: &String,
+        source_file_path: &str,
+        target_file_path: &str,
     ) -> Result<String, EngineErrorMessage> {
-        let compiler_command = compiler
-            .command
-            .replace("${COMPILER}", compiler.path.as_str())
-            .replace("${COMPILER_ARGS}", compiler_args)
-            .replace(
-                "${SOURCE_FILES}",
-                Path::new(source_file_path.as_str())
-                    .absolutize()
-                    .unwrap()
-                    .to_str()
-                    .unwrap(),
-            )
-            .replace(
-                "${TARGET_FILE_NAME}",
-                Path::new(target_file_path.as_str())
-                    .absolutize()
-                    .unwrap()
-                    .to_str()
-                    .unwrap(),
-            );
+        let (compiler_stdout, compiler_stderr) =
+            self.run_compiler(compiler_args, compiler, source_file_path, target_file_path)?;
 
-        let compiler_output = if cfg!(target_os = "windows") {
-            Command::new("powershell")
-                .arg("-Command")
-                .arg(&format!("&{{{}}}", compiler_command.as_str()))
-                .output()
+        if compiler_stdout.is_empty() && compiler_stderr.is_empty() {
+            Ok("compile ok".to_string())
+        } else if compiler_stderr.is_empty() {
+            Ok(compiler_stdout)
         } else {
-            Command::new("sh")
-                .arg("-c")
-                .arg(&format!("&{{{}}}", compiler_command.as_str()))
-                .output()
-        };
-
-        if compiler_output.is_err() {
-            return Err(format!(
-                "run command failed, {}",
-                compiler_output.err().unwrap()
-            ));
+            Err(compiler_stderr)
         }
+    }
 
-        let compiler_output = compiler_output.unwrap();
-        let compiler_stderr = String::from_utf8(compiler_output.stderr).unwrap();
-        let compiler_stdout = String::from_utf8(compiler_output.stdout).unwrap();
+    /// Stdout and stderr of a successful compilation.
+    fn run_compiler(
+        &self,
+        compiler_args: &[String],
+        compiler: &EngineCompiler,
+        source_file_path: &str,
+        target_file_path: &str,
+    ) -> Result<(String, String), EngineErrorMessage> {
+        let target_file_path = Self::absolute_path(target_file_path)?;
+        let working_directory = Path::new(&target_file_path)
+            .parent()
+            .unwrap_or_else(|| Path::new("."));
+
+        let argv = compiler.command.expand(
+            &compiler.path,
+            compiler_args,
+            &Self::absolute_path(source_file_path)?,
+            &target_file_path,
+        );
+
+        let compiler_output = self.platform.run(&argv, working_directory);
+
+        let compiler_output = compiler_output.map_err(|e| format!("run command failed, {}", e))?;
+        let compiler_stderr = String::from_utf8_lossy(&compiler_output.stderr).into_owned();
+        let compiler_stdout = String::from_utf8_lossy(&compiler_output.stdout).into_owned();
 
         if !compiler_output.status.success() {
             return Err(format!(
-                "compile failed, code {}\n{}",
-                compiler_output.status, compiler_stderr
+                "compile failed, {}\n{}",
+                self.platform.describe_status(&compiler_output.status),
+                compiler_stderr
             ));
         }
 
-        if compiler_stdout.is_empty() && compiler_stderr.is_empty() {
-            Ok("compile ok".to_string())
-        } else if compiler_stderr.is_empty() {
-            Ok(compiler_stdout)
-        } else {
-            Err(compiler_stderr)
-        }
+        Ok((compiler_stdout, compiler_stderr))
+    }
+
+    fn absolute_path<P: AsRef<Path>>(path: P) -> Result<String, EngineErrorMessage> {
+        let path = path.as_ref();
+
+        path.absolutize()
+            .ok()
+            .and_then(|p| p.to_str().map(|p| p.to_string()))
+            .ok_or_else(|| format!("invalid path: {}", path.display()))
     }
 
     fn setup_execution_env(
         &self,
+        task_uid: &str,
         compiler: &str,
         source_code: &str,
         file_name: &str,
-    ) -> Result<(&EngineCompiler, String, String), EngineErrorMessage> {
-        let file_extension = Path::new(file_name)
+    ) -> Result<ExecutionEnv<'_>, EngineErrorMessage> {
+        let file_name = validate_file_name(file_name)?;
+        let file_path = Path::new(file_name);
+        let invalid_file_name = || format!("invalid file name: {}", file_name);
+
+        let file_extension = file_path
             .extension()
-            .expect("invalid file name")
-            .to_str()
-            .unwrap();
+            .and_then(|e| e.to_str())
+            .ok_or_else(invalid_file_name)?;
 
-        let compiler_option = self
+        let compiler = self
             .compilers
-            .as_slice()
-            .into_iter()
-            .find(|c| c.name.as_str() == compiler && c.extensions.contains(file_extension));
-
-        if compiler_option.is_none() {
-            return Err("unable to find specific compiler".to_string());
-        }
+            .iter()
+            .find(|c| c.name.as_str() == compiler && c.extensions.contains(file_extension))
+            .ok_or_else(|| "unable to find specific compiler".to_string())?;
 
-        let compiler = compiler_option.unwrap();
-        let file_base_name = Path::new(file_name)
+        let file_base_name = file_path
             .file_stem()
-            .expect("invalid file name")
-            .to_str()
-            .unwrap();
+            .and_then(|s| s.to_str())
+            .ok_or_else(invalid_file_name)?;
 
-        let source_file_path_buf = Path::new(self.source_directory.as_str()).join(file_name);
-        let source_file_path = source_file_path_buf.as_path();
+        let source_workspace = create_task_workspace(Path::new(&self.source_directory), task_uid)?;
+        let build_workspace = create_task_workspace(Path::new(&self.build_directory), task_uid)?;
 
-        fs::write(source_file_path, source_code).expect("unable to write source file");
+        let source_file_path_buf = write_workspace_file(&source_workspace, file_name, source_code)?;
+        let target_file_path_buf = workspace_file(&build_workspace, file_base_name)?;
 
-        let target_file_path_buf = Path::new(self.build_directory.as_str()).join(file_base_name);
-        let target_file_path = target_file_path_buf.as_path();
+        let source_file_path = source_file_path_buf
+            .to_str()
+            .ok_or_else(invalid_file_name)?
+            .to_string();
+        let target_file_path = target_file_path_buf
+            .to_str()
+            .ok_or_else(invalid_file_name)?
+            .to_string();
 
-        Ok((
+        Ok(ExecutionEnv {
             compiler,
-            source_file_path.to_str().unwrap().to_string(),
-            target_file_path.to_str().unwrap().to_string(),
-        ))
+            source_file_path,
+            target_file_path,
+            _workspaces: (source_workspace, build_workspace),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+
+    fn test_engine(name: &str) -> (Engine, PathBuf) {
+        let root = env::temp_dir().join(format!("qbmr-engine-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        let compilers = vec![CompilerConfig {
+            name: "cc".to_string(),
+            path: "cc".to_string(),
+            version: "1.0.0".to_string(),
+            command: "${COMPILER} ${SOURCE_FILES} -o ${TARGET_FILE_NAME}".to_string(),
+            extensions: vec!["c".to_string()],
+        }];
+
+        let engine = Engine::new(
+            &compilers,
+            &ArgPolicy::default(),
+            root.join("cache").to_str().unwrap(),
+            root.join("build").to_str().unwrap(),
+        )
+        .unwrap();
+
+        (engine, root)
+    }
+
+    #[test]
+    fn rejects_traversal_in_file_name() {
+        let (engine, root) = test_engine("traversal");
+
+        for file_name in [
+            "../../escape.c",
+            "../escape.c",
+            "/tmp/escape.c",
+            "sub/escape.c",
+        ] {
+            let result = engine.execute("0123456789abcdef", "cc-1.0.0", "", "", "", file_name);
+
+            assert!(
+                matches!(result, EngineResult::EngineExecutionSetupError(_)),
+                "{:?} accepted: {:?}",
+                file_name,
+                result
+            );
+        }
+
+        assert!(!root.join("escape.c").exists());
+        assert!(!Path::new("/tmp/escape.c").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn rejects_traversal_in_task_uid() {
+        let (engine, root) = test_engine("uid");
+
+        let result = engine.execute("../../../../tmp", "cc-1.0.0", "", "", "", "main.c");
+
+        assert!(matches!(result, EngineResult::EngineExecutionSetupError(_)));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn rejects_denied_compiler_args() {
+        let (engine, root) = test_engine("args");
+
+        for args in ["-fplugin=/tmp/evil.so", "-O2 -B/tmp", "-DX='unterminated"] {
+            let result = engine.execute("0123456789abcdef", "cc-1.0.0", args, "", "", "main.c");
+
+            assert!(
+                matches!(result, EngineResult::EngineExecutionSetupError(_)),
+                "{:?} accepted: {:?}",
+                args,
+                result
+            );
+        }
+
+        assert!(!root.join("cache").join("0123456789abcdef").exists());
+
+        fs::remove_dir_all(&root).unwrap();
     }
 }
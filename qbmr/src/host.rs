@@ -0,0 +1,170 @@
+use regex::Regex;
+use shared_child::SharedChild;
+use std::fs;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use sysinfo::{ProcessorExt, RefreshKind, System, SystemExt};
+use util::EnvironmentFingerprint;
+
+const COMPILER_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Host settings that affect timings, `None` where they cannot be read.
+pub struct HostState {
+    pub governor: Option<String>,
+    pub turbo: Option<bool>,
+    pub smt: Option<bool>,
+    /// `/proc/sys/kernel/randomize_va_space`, `0` is disabled.
+    pub aslr: Option<u8>,
+    pub load_average: [f64; 3],
+}
+
+pub fn collect_host_state() -> HostState {
+    let load = System::new().load_average();
+
+    HostState {
+        governor: read_sys_file("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor"),
+        turbo: turbo_enabled(),
+        smt: read_sys_file("/sys/devices/system/cpu/smt/active").map(|s| s == "1"),
+        aslr: read_sys_file("/proc/sys/kernel/randomize_va_space").and_then(|s| s.parse().ok()),
+        load_average: [load.one, load.five, load.fifteen],
+    }
+}
+
+/// What a compiler binary says it is.
+pub struct CompilerIdentity {
+    /// First line of `--version`, e.g. `g++ (Ubuntu 11.2.0-19ubuntu1) 11.2.0`.
+    pub name: String,
+    /// First version number in `name`.
+    pub version: String,
+}
+
+/// Runs `path --version`, `None` if it fails, hangs or prints no version.
+pub fn probe_compiler(path: &str) -> Option<CompilerIdentity> {
+    let child = SharedChild::spawn(
+        Command::new(path)
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null()),
+    )
+    .ok()?;
+
+    match child.wait_timeout(COMPILER_PROBE_TIMEOUT) {
+        Ok(Some(status)) if status.success() => {}
+        Ok(Some(_)) | Err(_) => return None,
+        Ok(None) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+    }
+
+    let mut output = String::new();
+    child.take_stdout()?.read_to_string(&mut output).ok()?;
+
+    parse_compiler_identity(&output)
+}
+
+fn parse_compiler_identity(output: &str) -> Option<CompilerIdentity> {
+    let name = output.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let version = Regex::new(r"\d+\.\d+(\.\d+)?").unwrap().find(name)?;
+
+    Some(CompilerIdentity {
+        name: name.to_string(),
+        version: version.as_str().to_string(),
+    })
+}
+
+/// Describes the host at the moment a task runs, it is never cached.
+pub fn collect_fingerprint(
+    compiler: &CompilerIdentity,
+    host: &HostState,
+) -> EnvironmentFingerprint {
+    let mut sys = System::new_with_specifics(RefreshKind::new().with_cpu().with_memory());
+
+    sys.refresh_cpu();
+    sys.refresh_memory();
+
+    let cpu = sys.global_processor_info();
+    let memory_used = if sys.total_memory() > 0 {
+        1.0 - sys.available_memory() as f64 / sys.total_memory() as f64
+    } else {
+        0.0
+    };
+
+    EnvironmentFingerprint {
+        host_name: sys.host_name().unwrap_or_default(),
+        cpu_vendor: cpu.vendor_id().to_string(),
+        cpu_brand: cpu.brand().trim().to_string(),
+        cpu_cores: sys.physical_core_count().unwrap_or_default() as u64,
+        cpu_logical_cores: sys.processors().len() as u64,
+        governor: host.governor.clone(),
+        turbo: host.turbo,
+        smt: host.smt,
+        aslr: host.aslr,
+        kernel_version: sys.kernel_version().unwrap_or_default(),
+        compiler: compiler.name.clone(),
+        compiler_version: compiler.version.clone(),
+        load_average: host.load_average,
+        memory_used,
+        memory_pressure: read_sys_file("/proc/pressure/memory")
+            .and_then(|p| parse_memory_pressure(&p)),
+    }
+}
+
+fn read_sys_file(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// `intel_pstate` reports the inverse of what `acpi-cpufreq` does.
+fn turbo_enabled() -> Option<bool> {
+    if let Some(no_turbo) = read_sys_file("/sys/devices/system/cpu/intel_pstate/no_turbo") {
+        return Some(no_turbo == "0");
+    }
+
+    read_sys_file("/sys/devices/system/cpu/cpufreq/boost").map(|boost| boost == "1")
+}
+
+/// Reads `avg10` of the `some` line, e.g.
+/// `some avg10=0.00 avg60=0.00 avg300=0.00 total=0`.
+fn parse_memory_pressure(pressure: &str) -> Option<f64> {
+    pressure
+        .lines()
+        .find(|l| l.starts_with("some "))?
+        .split_whitespace()
+        .find_map(|f| f.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_memory_pressure() {
+        let pressure = "some avg10=1.25 avg60=0.50 avg300=0.10 total=1234\n\
+                        full avg10=0.75 avg60=0.20 avg300=0.05 total=567\n";
+
+        assert_eq!(parse_memory_pressure(pressure), Some(1.25));
+        assert_eq!(parse_memory_pressure(""), None);
+    }
+
+    #[test]
+    fn parses_compiler_identities() {
+        let gcc = parse_compiler_identity(
+            "g++ (Ubuntu 11.2.0-19ubuntu1) 11.2.0\nCopyright (C) 2021 Free Software Foundation\n",
+        )
+        .unwrap();
+        assert_eq!(gcc.name, "g++ (Ubuntu 11.2.0-19ubuntu1) 11.2.0");
+        assert_eq!(gcc.version, "11.2.0");
+
+        let clang =
+            parse_compiler_identity("\nUbuntu clang version 14.0.0-1ubuntu1\nTarget: x\n").unwrap();
+        assert_eq!(clang.version, "14.0.0");
+
+        assert!(parse_compiler_identity("no version here\n").is_none());
+        assert!(probe_compiler("/qbm/no/such/compiler").is_none());
+    }
+}
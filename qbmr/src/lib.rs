@@ -0,0 +1,33 @@
+mod command;
+mod conf;
+mod discover;
+mod engine;
+mod host;
+mod platform;
+mod readiness;
+
+pub use crate::conf::config_errors;
+pub use crate::conf::default_iteration_timeout_secs;
+pub use crate::conf::setup_config;
+pub use crate::conf::validate_compilers;
+pub use crate::conf::CompilerConfig;
+pub use crate::conf::Config;
+pub use crate::discover::system_compiler_info;
+pub use crate::discover::system_compilers;
+pub use crate::discover::SystemCompiler;
+pub use crate::discover::SYSTEM_COMPILER_COMMAND;
+pub use crate::discover::SYSTEM_COMPILER_EXTENSIONS;
+pub use crate::engine::BenchmarkObserver;
+pub use crate::engine::Engine;
+pub use crate::engine::EngineErrorMessage;
+pub use crate::engine::EngineResult;
+pub use crate::engine::RunningProgram;
+pub use crate::engine::TASK_CANCELLED;
+pub use crate::host::collect_fingerprint;
+pub use crate::host::collect_host_state;
+pub use crate::host::probe_compiler;
+pub use crate::host::CompilerIdentity;
+pub use crate::host::HostState;
+pub use crate::platform::Platform;
+pub use crate::readiness::ReadinessMode;
+pub use crate::readiness::ReadinessPolicy;
@@ -1,15 +1,36 @@
-use crate::conf::setup_config;
-use crate::engine::Engine;
-
-mod conf;
-mod engine;
+use qbmr::{setup_config, Engine, EngineResult};
+use std::process;
+use std::time::Duration;
 
 fn main() {
-    let config = setup_config().expect("unable to load config.");
+    let config = match setup_config() {
+        Ok(config) => config,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("qbmr.toml: {}", error);
+            }
+
+            process::exit(1);
+        }
+    };
 
-    let engine = Engine::new(&config.compilers, "./cache/", "./build").unwrap();
+    let engine = match Engine::new(
+        &config.compilers,
+        &config.compiler_args,
+        "./cache/",
+        "./build",
+    ) {
+        Ok(engine) => {
+            engine.with_iteration_timeout(Duration::from_secs(config.iteration_timeout_secs))
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    };
 
     let result = engine.execute(
+        "00000000-0000-0000-0000-000000000000",
         "clang++-13.0.0",
         "",
         "#include<cstdio>\nint main(){ printf(\"%d\\n\", 5);\n return 0; }",
@@ -17,5 +38,10 @@ fn main() {
         "main.cpp",
     );
 
-    println!("{:?}", result)
+    match result {
+        EngineResult::EngineExecutionOk(output) => println!("{}", output),
+        EngineResult::EngineExecutionSetupError(message)
+        | EngineResult::EngineCompileError(message)
+        | EngineResult::EngineExecutionError(message) => eprintln!("{}", message),
+    }
 }
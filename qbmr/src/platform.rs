@@ -0,0 +1,88 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+
+/// Operating system specific parts of compiling and running a task.
+pub trait Platform: Send + Sync {
+    /// Path of the executable a compiler produces for `target`.
+    fn executable_file(&self, target: &Path) -> PathBuf;
+
+    /// Human readable description of a process exit status.
+    fn describe_status(&self, status: &ExitStatus) -> String;
+
+    /// Runs `argv` directly, without any shell in between.
+    fn run(&self, argv: &[String], working_directory: &Path) -> io::Result<Output> {
+        let (program, args) = argv
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+
+        Command::new(program)
+            .args(args)
+            .current_dir(working_directory)
+            .output()
+    }
+
+    /// Starts `argv` like `run` without waiting for it, stdout and stderr
+    /// are piped.
+    fn spawn(&self, argv: &[String], working_directory: &Path) -> io::Result<Child> {
+        let (program, args) = argv
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+
+        Command::new(program)
+            .args(args)
+            .current_dir(working_directory)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+    }
+}
+
+pub struct Posix;
+
+impl Platform for Posix {
+    fn executable_file(&self, target: &Path) -> PathBuf {
+        target.to_path_buf()
+    }
+
+    #[cfg(unix)]
+    fn describe_status(&self, status: &ExitStatus) -> String {
+        use std::os::unix::process::ExitStatusExt;
+
+        match (status.code(), status.signal()) {
+            (Some(code), _) => format!("exit code {}", code),
+            (None, Some(signal)) => format!("killed by signal {}", signal),
+            (None, None) => status.to_string(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn describe_status(&self, status: &ExitStatus) -> String {
+        status.to_string()
+    }
+}
+
+pub struct Windows;
+
+impl Platform for Windows {
+    fn executable_file(&self, target: &Path) -> PathBuf {
+        target.with_extension("exe")
+    }
+
+    fn describe_status(&self, status: &ExitStatus) -> String {
+        match status.code() {
+            Some(code) => format!("exit code {:#x}", code),
+            None => status.to_string(),
+        }
+    }
+}
+
+/// The platform qbmr was built for.
+pub fn native() -> Box<dyn Platform> {
+    if cfg!(target_os = "windows") {
+        Box::new(Windows)
+    } else {
+        Box::new(Posix)
+    }
+}
@@ -0,0 +1,181 @@
+use crate::host::HostState;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessMode {
+    Off,
+    /// Failed checks are reported with the result.
+    Warn,
+    /// Tasks are compiled but not run while a check fails.
+    Enforce,
+}
+
+/// What a host has to look like before it is trusted with timings.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ReadinessPolicy {
+    pub mode: ReadinessMode,
+    pub governor: Option<String>,
+    /// Required turbo/boost state, `None` accepts either.
+    pub turbo: Option<bool>,
+    /// Highest acceptable 1 minute load average.
+    pub max_load: Option<f64>,
+}
+
+impl Default for ReadinessPolicy {
+    fn default() -> Self {
+        ReadinessPolicy {
+            mode: ReadinessMode::Warn,
+            governor: Some("performance".to_string()),
+            turbo: None,
+            max_load: Some(1.0),
+        }
+    }
+}
+
+impl ReadinessPolicy {
+    /// Every check `host` fails, empty when the policy is off. Governor,
+    /// turbo and ASLR are read from `/sys` and `/proc`, elsewhere they are
+    /// unsupported and skipped rather than failed.
+    pub fn check(&self, host: &HostState) -> Vec<String> {
+        self.check_on(host, cfg!(target_os = "linux"))
+    }
+
+    fn check_on(&self, host: &HostState, linux: bool) -> Vec<String> {
+        let mut failures = vec![];
+
+        if self.mode == ReadinessMode::Off {
+            return failures;
+        }
+
+        if linux {
+            failures.extend(self.check_kernel_settings(host));
+        }
+
+        if let Some(max_load) = self.max_load {
+            if host.load_average[0] > max_load {
+                failures.push(format!(
+                    "load average {:.2} exceeds {:.2}",
+                    host.load_average[0], max_load
+                ));
+            }
+        }
+
+        failures
+    }
+
+    fn check_kernel_settings(&self, host: &HostState) -> Vec<String> {
+        let mut failures = vec![];
+
+        if let Some(governor) = &self.governor {
+            match &host.governor {
+                Some(g) if g == governor => {}
+                Some(g) => failures.push(format!(
+                    "CPU frequency governor is {}, expected {}",
+                    g, governor
+                )),
+                None => failures.push("CPU frequency governor is unknown".to_string()),
+            }
+        }
+
+        if let Some(turbo) = self.turbo {
+            match host.turbo {
+                Some(t) if t == turbo => {}
+                Some(t) => failures.push(format!(
+                    "turbo boost is {}, expected {}",
+                    on_off(t),
+                    on_off(turbo)
+                )),
+                None => failures.push("turbo boost state is unknown".to_string()),
+            }
+        }
+
+        if host.aslr.is_none() {
+            failures.push("ASLR state is unknown".to_string());
+        }
+
+        failures
+    }
+}
+
+fn on_off(setting: bool) -> &'static str {
+    if setting {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host() -> HostState {
+        HostState {
+            governor: Some("performance".to_string()),
+            turbo: Some(false),
+            smt: Some(true),
+            aslr: Some(2),
+            load_average: [0.2, 0.1, 0.1],
+        }
+    }
+
+    #[test]
+    fn accepts_a_quiet_host() {
+        let policy = ReadinessPolicy {
+            turbo: Some(false),
+            ..Default::default()
+        };
+
+        assert!(policy.check(&host()).is_empty());
+    }
+
+    #[test]
+    fn reports_every_failed_check() {
+        let policy = ReadinessPolicy {
+            turbo: Some(false),
+            ..Default::default()
+        };
+        let host = HostState {
+            governor: Some("powersave".to_string()),
+            turbo: Some(true),
+            aslr: None,
+            load_average: [3.5, 2.0, 1.0],
+            ..host()
+        };
+
+        assert_eq!(
+            policy.check(&host),
+            [
+                "CPU frequency governor is powersave, expected performance",
+                "turbo boost is on, expected off",
+                "ASLR state is unknown",
+                "load average 3.50 exceeds 1.00",
+            ]
+        );
+
+        let policy = ReadinessPolicy {
+            mode: ReadinessMode::Off,
+            ..policy
+        };
+        assert!(policy.check(&host).is_empty());
+    }
+
+    #[test]
+    fn skips_kernel_settings_outside_linux() {
+        let policy = ReadinessPolicy {
+            turbo: Some(false),
+            ..Default::default()
+        };
+        let host = HostState {
+            governor: None,
+            turbo: None,
+            aslr: None,
+            ..host()
+        };
+
+        assert_eq!(policy.check_on(&host, true).len(), 3);
+        assert!(policy.check_on(&host, false).is_empty());
+    }
+}
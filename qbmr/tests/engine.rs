@@ -0,0 +1,396 @@
+#![cfg(unix)]
+
+use qbmr::{
+    BenchmarkObserver, CompilerConfig, Engine, EngineResult, Platform, ReadinessMode,
+    ReadinessPolicy,
+};
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use util::{ArgPolicy, BenchmarkTask, NodeConstraints, QBM_PROTOCOL_VERSION};
+
+const TASK_UID: &str = "0123456789abcdef";
+
+/// Pretends to compile a shell script by copying it to the `-o` target,
+/// every other argument is logged to `<script>.log` one per line.
+const STUB_COMPILER: &str = r#"#!/bin/sh
+out=""
+src=""
+while [ $# -gt 0 ]; do
+    case "$1" in
+        -o) out="$2"; shift 2 ;;
+        --version) echo "stub 2.1.0"; exit 0 ;;
+        --fail) echo "stub: forced failure" >&2; exit 1 ;;
+        *) echo "$1" >> "$0.log"; src="$1"; shift ;;
+    esac
+done
+cp "$src" "$out" && chmod +x "$out"
+"#;
+
+/// Runs commands like a POSIX host but records them, names the executable
+/// `<target>.bin` and describes exit statuses its own way.
+#[derive(Clone, Default)]
+struct FakePlatform {
+    commands: Arc<Mutex<Vec<Vec<String>>>>,
+}
+
+impl Platform for FakePlatform {
+    fn executable_file(&self, target: &Path) -> PathBuf {
+        target.with_extension("bin")
+    }
+
+    fn describe_status(&self, status: &ExitStatus) -> String {
+        format!("fake status {:?}", status.code())
+    }
+
+    fn run(&self, argv: &[String], working_directory: &Path) -> io::Result<Output> {
+        self.commands.lock().unwrap().push(argv.to_vec());
+
+        // The stub compiler writes to the target, move it where this
+        // platform expects the executable.
+        let output = Command::new(&argv[0])
+            .args(&argv[1..])
+            .current_dir(working_directory)
+            .output()?;
+
+        let target = argv.iter().position(|a| a == "-o");
+
+        if let (true, Some(i)) = (output.status.success(), target) {
+            let target = Path::new(&argv[i + 1]);
+            fs::rename(target, self.executable_file(target))?;
+        }
+
+        Ok(output)
+    }
+
+    fn spawn(&self, argv: &[String], working_directory: &Path) -> io::Result<Child> {
+        self.commands.lock().unwrap().push(argv.to_vec());
+
+        Command::new(&argv[0])
+            .args(&argv[1..])
+            .current_dir(working_directory)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+    }
+}
+
+struct Fixture {
+    root: PathBuf,
+    engine: Engine,
+    platform: FakePlatform,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Fixture {
+        Fixture::with_timeout(name, Duration::from_secs(60))
+    }
+
+    fn with_timeout(name: &str, timeout: Duration) -> Fixture {
+        Fixture::build(name, timeout, ReadinessPolicy::default())
+    }
+
+    fn with_readiness(name: &str, readiness: ReadinessPolicy) -> Fixture {
+        Fixture::build(name, Duration::from_secs(60), readiness)
+    }
+
+    fn build(name: &str, timeout: Duration, readiness: ReadinessPolicy) -> Fixture {
+        let root = env::temp_dir().join(format!("qbmr-it-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        let compiler = root.join("stubcc");
+        fs::write(&compiler, STUB_COMPILER).unwrap();
+        fs::set_permissions(&compiler, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let compilers = vec![CompilerConfig {
+            name: "stub".to_string(),
+            path: compiler.to_str().unwrap().to_string(),
+            version: "1.0.0".to_string(),
+            command: "${COMPILER} ${COMPILER_ARGS} ${SOURCE_FILES} -o ${TARGET_FILE_NAME}"
+                .to_string(),
+            extensions: vec!["sh".to_string()],
+        }];
+
+        let platform = FakePlatform::default();
+        let engine = Engine::with_platform(
+            &compilers,
+            &ArgPolicy::default(),
+            Box::new(platform.clone()),
+            root.join("cache").to_str().unwrap(),
+            root.join("build").to_str().unwrap(),
+        )
+        .unwrap()
+        .with_iteration_timeout(timeout)
+        .with_readiness(readiness);
+
+        Fixture {
+            root,
+            engine,
+            platform,
+        }
+    }
+
+    fn execute(
+        &self,
+        compiler_args: &str,
+        source_code: &str,
+        execution_args: &str,
+    ) -> EngineResult {
+        self.engine.execute(
+            TASK_UID,
+            "stub-1.0.0",
+            compiler_args,
+            source_code,
+            execution_args,
+            "main.sh",
+        )
+    }
+
+    fn compiler_log(&self) -> Vec<String> {
+        fs::read_to_string(self.root.join("stubcc.log"))
+            .unwrap_or_default()
+            .lines()
+            .map(|l| l.to_string())
+            .collect()
+    }
+
+    fn commands(&self) -> Vec<Vec<String>> {
+        self.platform.commands.lock().unwrap().clone()
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[test]
+fn compiles_and_runs_program() {
+    let fixture = Fixture::new("run");
+
+    let result = fixture.execute("-O2", "#!/bin/sh\necho \"hello $*\"\n", "a 'b c'");
+
+    match result {
+        EngineResult::EngineExecutionOk(output) => assert_eq!(output, "hello a b c\n"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let commands = fixture.commands();
+    assert_eq!(commands.len(), 2);
+    assert!(commands[1][0].ends_with("main.bin"), "{:?}", commands[1]);
+    assert_eq!(commands[1][1..], ["a", "b c"]);
+}
+
+#[test]
+fn passes_compiler_args_as_separate_words() {
+    let fixture = Fixture::new("args");
+    let pwned = fixture.path("pwned");
+
+    let compiler_args = format!("-O2 '-DNAME=a b' ; touch {}", pwned.display());
+    let result = fixture.execute(&compiler_args, "#!/bin/sh\n", "");
+
+    assert!(
+        matches!(result, EngineResult::EngineExecutionOk(_)),
+        "{:?}",
+        result
+    );
+    assert!(!Path::new(&pwned).exists());
+
+    let log = fixture.compiler_log();
+    assert_eq!(log[..4], ["-O2", "-DNAME=a b", ";", "touch"]);
+    assert!(log.last().unwrap().ends_with("main.sh"));
+}
+
+#[test]
+fn reports_compile_errors() {
+    let fixture = Fixture::new("compile-error");
+
+    match fixture.execute("--fail", "#!/bin/sh\n", "") {
+        EngineResult::EngineCompileError(message) => {
+            assert!(message.contains("fake status Some(1)"), "{}", message);
+            assert!(message.contains("stub: forced failure"), "{}", message);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn reports_execution_errors() {
+    let fixture = Fixture::new("execution-error");
+
+    match fixture.execute("", "#!/bin/sh\necho oops >&2\nexit 3\n", "") {
+        EngineResult::EngineExecutionError(message) => {
+            assert!(message.contains("fake status Some(3)"), "{}", message);
+            assert!(message.contains("oops"), "{}", message);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn rejects_denied_compiler_args_before_compiling() {
+    let fixture = Fixture::new("denied");
+
+    let result = fixture.execute("-fplugin=/tmp/evil.so", "#!/bin/sh\n", "");
+
+    assert!(matches!(result, EngineResult::EngineExecutionSetupError(_)));
+    assert!(fixture.compiler_log().is_empty());
+    assert!(fixture.commands().is_empty());
+}
+
+fn task(source_code: &str, iterations: u32) -> BenchmarkTask {
+    BenchmarkTask {
+        protocol_version: QBM_PROTOCOL_VERSION,
+        task_uid: TASK_UID.into(),
+        compiler: "stub".into(),
+        compiler_version: "1.0.0".into(),
+        source_file: "main.sh".into(),
+        source_code: source_code.into(),
+        compiler_args: "-O2".into(),
+        assembly: false,
+        iterations,
+        name: None,
+        user: None,
+        tags: vec![],
+        constraints: NodeConstraints::default(),
+    }
+}
+
+#[test]
+fn benchmarks_every_iteration() {
+    let fixture = Fixture::new("benchmark");
+    let task = task("#!/bin/sh\necho hello\n", 3);
+
+    let mut iterations = vec![];
+    let result = fixture
+        .engine
+        .benchmark(&task, |iteration, _| iterations.push(iteration));
+
+    assert_eq!(result.output, "hello\n", "{}", result.message);
+    assert_eq!(result.timings.len(), 3);
+    assert_eq!(iterations, [1, 2, 3]);
+
+    let fingerprint = result.fingerprint.unwrap();
+    assert_eq!(fingerprint.compiler, "stub 2.1.0");
+    assert_eq!(fingerprint.compiler_version, "2.1.0");
+}
+
+#[test]
+fn unidentified_compilers_do_not_fail_enforced_readiness() {
+    // Every check is off, the host is always ready.
+    let fixture = Fixture::with_readiness(
+        "unidentified",
+        ReadinessPolicy {
+            mode: ReadinessMode::Enforce,
+            governor: None,
+            turbo: None,
+            max_load: None,
+        },
+    );
+    fs::write(
+        fixture.path("stubcc"),
+        STUB_COMPILER.replace(r#"echo "stub 2.1.0"; exit 0"#, "exit 1"),
+    )
+    .unwrap();
+
+    let result = fixture
+        .engine
+        .benchmark(&task("#!/bin/sh\necho hello\n", 2), |_, _| {});
+
+    assert_eq!(result.timings.len(), 2, "{}", result.message);
+    assert_eq!(result.warnings.len(), 1);
+    assert!(result.warnings[0].starts_with("unable to identify compiler"));
+    assert_eq!(result.fingerprint.unwrap().compiler_version, "1.0.0");
+}
+
+#[test]
+fn drops_output_beyond_the_limit() {
+    let fixture = Fixture::new("truncate");
+    let task = task(
+        "#!/bin/sh\nhead -c 3000000 /dev/zero\nhead -c 3000000 /dev/zero >&2\n",
+        1,
+    );
+
+    let result = fixture.engine.benchmark(&task, |_, _| {});
+
+    assert_eq!(result.timings.len(), 1, "{}", result.message);
+    assert!(result.output.len() < 1024 * 1024 + 100);
+    assert!(result
+        .output
+        .ends_with("\n[1951424 more bytes of output dropped]\n"));
+}
+
+#[test]
+fn kills_programs_running_too_long() {
+    let fixture = Fixture::with_timeout("timeout", Duration::from_millis(300));
+    let task = task("#!/bin/sh\nexec sleep 10\n", 2);
+
+    let start = Instant::now();
+    let result = fixture.engine.benchmark(&task, |_, _| {});
+
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(
+        result
+            .message
+            .contains("timed out after 300ms in iteration 1"),
+        "{}",
+        result.message
+    );
+    assert!(result.timings.is_empty());
+}
+
+/// When each chunk of output arrived and when each iteration finished.
+#[derive(Default)]
+struct Outputs {
+    chunks: Vec<(u32, String, Instant)>,
+    finished: Vec<Instant>,
+}
+
+impl BenchmarkObserver for &mut Outputs {
+    fn output(&mut self, iteration: u32, output: &str) {
+        self.chunks
+            .push((iteration, output.to_string(), Instant::now()));
+    }
+
+    fn iteration(&mut self, _iteration: u32, _elapsed_ns: u64) {
+        self.finished.push(Instant::now());
+    }
+}
+
+#[test]
+fn streams_output_of_every_iteration() {
+    let fixture = Fixture::new("stream");
+    let task = task("#!/bin/sh\necho first\nsleep 0.3\necho second\n", 2);
+
+    let mut outputs = Outputs::default();
+    let result = fixture.engine.benchmark(&task, &mut outputs);
+
+    assert_eq!(result.output, "first\nsecond\n", "{}", result.message);
+
+    for iteration in [1, 2] {
+        let output = outputs
+            .chunks
+            .iter()
+            .filter(|(i, _, _)| *i == iteration)
+            .map(|(_, output, _)| output.as_str())
+            .collect::<String>();
+
+        assert_eq!(output, "first\nsecond\n");
+    }
+
+    // The first line arrived while the first run was still sleeping.
+    let (_, first, arrived) = &outputs.chunks[0];
+    assert_eq!(first, "first\n");
+    assert!(*arrived + Duration::from_millis(200) < outputs.finished[0]);
+}
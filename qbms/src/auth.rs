@@ -0,0 +1,60 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+
+/// The `Authorization: Bearer <secrets>` header of a request. Routes that
+/// change the server or its cluster check it against the configured secrets.
+pub struct BearerToken(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BearerToken {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(|t| t.trim().to_string());
+
+        Outcome::Success(BearerToken(token))
+    }
+}
+
+impl BearerToken {
+    pub fn check(&self, secrets: &str) -> Result<(), (Status, String)> {
+        match &self.0 {
+            Some(token) if !secrets.is_empty() && same_secret(token, secrets) => Ok(()),
+            Some(_) => Err((Status::Unauthorized, "wrong secrets".to_string())),
+            None => Err((
+                Status::Unauthorized,
+                "missing Authorization: Bearer <secrets> header".to_string(),
+            )),
+        }
+    }
+}
+
+/// Compares every byte so the time taken does not hint at the secret.
+fn same_secret(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_tokens_against_secrets() {
+        assert!(BearerToken(Some("s3cret".to_string()))
+            .check("s3cret")
+            .is_ok());
+        assert!(BearerToken(Some("s3cre".to_string()))
+            .check("s3cret")
+            .is_err());
+        assert!(BearerToken(Some("".to_string())).check("").is_err());
+        assert!(BearerToken(None).check("s3cret").is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use util::{mean, welch_t_test, BaselineComparison, BenchmarkInfo, TaskRecord};
+
+/// Timings each side needs before a slowdown can be tested for significance.
+const MIN_SAMPLES: usize = 2;
+
+/// When a result counts as a regression against its baseline.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RegressionPolicy {
+    /// Relative slowdown of the mean that is tolerated, `0.05` is 5%.
+    pub threshold: f64,
+    /// Significance level of the one-sided Welch's t-test.
+    pub significance: f64,
+}
+
+impl Default for RegressionPolicy {
+    fn default() -> Self {
+        RegressionPolicy {
+            threshold: 0.05,
+            significance: 0.05,
+        }
+    }
+}
+
+impl RegressionPolicy {
+    /// Compares `result` against `baseline`, `None` if either has no timings.
+    ///
+    /// With fewer than `MIN_SAMPLES` timings on either side no test can be
+    /// done, the result is never a regression and a warning says why.
+    pub fn compare(
+        &self,
+        baseline: &TaskRecord,
+        result: &BenchmarkInfo,
+    ) -> Option<BaselineComparison> {
+        if baseline.result.timings.is_empty() || result.timings.is_empty() {
+            return None;
+        }
+
+        let baseline_timings = to_f64(&baseline.result.timings);
+        let timings = to_f64(&result.timings);
+
+        let baseline_mean_ns = mean(&baseline_timings);
+        let mean_ns = mean(&timings);
+        let change = mean_ns / baseline_mean_ns - 1.0;
+        let p_value = welch_t_test(&baseline_timings, &timings);
+
+        let mut warnings = match (&baseline.result.fingerprint, &result.fingerprint) {
+            (Some(a), Some(b)) => a.differences(b),
+            _ => vec![],
+        };
+
+        let regression = if baseline_timings.len() < MIN_SAMPLES || timings.len() < MIN_SAMPLES {
+            warnings.push(format!(
+                "insufficient samples to test for a regression, {} baseline and {} new \
+                 timings, at least {} each are needed",
+                baseline_timings.len(),
+                timings.len(),
+                MIN_SAMPLES
+            ));
+            false
+        } else {
+            change > self.threshold && p_value.is_some_and(|p| p < self.significance)
+        };
+
+        Some(BaselineComparison {
+            baseline_uid: baseline.task_uid.clone(),
+            baseline_mean_ns,
+            mean_ns,
+            change,
+            p_value,
+            regression,
+            warnings,
+        })
+    }
+}
+
+fn to_f64(timings: &[u64]) -> Vec<f64> {
+    timings.iter().map(|t| *t as f64).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::EnvironmentFingerprint;
+
+    fn info(timings: &[u64]) -> BenchmarkInfo {
+        BenchmarkInfo {
+            timings: timings.to_vec(),
+            ..BenchmarkInfo::new("task-b")
+        }
+    }
+
+    fn baseline(timings: &[u64]) -> TaskRecord {
+        TaskRecord {
+            task_uid: "task-a".to_string(),
+            compiler: "g++".to_string(),
+            compiler_version: "1.0.0".to_string(),
+            source_file: "main.cpp".to_string(),
+            source_code: "".to_string(),
+            compiler_args: "".to_string(),
+            assembly: false,
+            iterations: timings.len() as u32,
+            name: Some("fib".to_string()),
+            user: None,
+            tags: vec![],
+            created_at: 0,
+            environment: None,
+            result: info(timings),
+        }
+    }
+
+    #[test]
+    fn flags_significant_slowdowns() {
+        let policy = RegressionPolicy::default();
+        let baseline = baseline(&[1000, 1010, 990, 1005, 995]);
+
+        let slower = policy
+            .compare(&baseline, &info(&[1100, 1110, 1090, 1105, 1095]))
+            .unwrap();
+        assert!(slower.regression);
+        assert!((slower.change - 0.1).abs() < 1e-9);
+
+        let noisy = policy
+            .compare(&baseline, &info(&[700, 1500, 900, 1400, 800]))
+            .unwrap();
+        assert!(!noisy.regression);
+
+        let faster = policy
+            .compare(&baseline, &info(&[900, 910, 890, 905, 895]))
+            .unwrap();
+        assert!(!faster.regression);
+    }
+
+    #[test]
+    fn warns_about_different_machines() {
+        let fingerprint = EnvironmentFingerprint {
+            host_name: "bench-1".to_string(),
+            cpu_vendor: "GenuineIntel".to_string(),
+            cpu_brand: "Intel(R) Core(TM) i7-8700".to_string(),
+            cpu_cores: 6,
+            cpu_logical_cores: 12,
+            governor: Some("performance".to_string()),
+            turbo: Some(false),
+            smt: Some(true),
+            aslr: Some(2),
+            kernel_version: "5.14.0".to_string(),
+            compiler: "g++".to_string(),
+            compiler_version: "11.2.0".to_string(),
+            load_average: [0.1, 0.1, 0.1],
+            memory_used: 0.2,
+            memory_pressure: None,
+        };
+
+        let mut baseline = baseline(&[1000, 1010]);
+        baseline.result.fingerprint = Some(fingerprint.clone());
+
+        let mut result = info(&[1000, 1010]);
+        result.fingerprint = Some(EnvironmentFingerprint {
+            load_average: [2.0, 1.0, 0.5],
+            ..fingerprint.clone()
+        });
+
+        let comparison = RegressionPolicy::default()
+            .compare(&baseline, &result)
+            .unwrap();
+        assert!(comparison.warnings.is_empty());
+
+        result.fingerprint = Some(EnvironmentFingerprint {
+            governor: Some("powersave".to_string()),
+            ..fingerprint.clone()
+        });
+
+        let comparison = RegressionPolicy::default()
+            .compare(&baseline, &result)
+            .unwrap();
+        assert_eq!(
+            comparison.warnings,
+            ["governor differs: performance vs powersave"]
+        );
+        assert_ne!(
+            result.fingerprint.unwrap().id(),
+            baseline.result.fingerprint.unwrap().id()
+        );
+    }
+
+    #[test]
+    fn needs_timings_on_both_sides() {
+        let policy = RegressionPolicy::default();
+
+        assert!(policy.compare(&baseline(&[]), &info(&[1000])).is_none());
+        assert!(policy.compare(&baseline(&[1000]), &info(&[])).is_none());
+    }
+
+    #[test]
+    fn needs_two_samples_per_side() {
+        let policy = RegressionPolicy::default();
+
+        for (baseline_timings, timings) in
+            [(&[1000][..], &[2000, 2010][..]), (&[1000, 1010], &[2000])]
+        {
+            let comparison = policy
+                .compare(&baseline(baseline_timings), &info(timings))
+                .unwrap();
+
+            assert!(!comparison.regression);
+            assert!(comparison.p_value.is_none());
+            assert!(comparison.warnings[0].starts_with("insufficient samples"));
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use crate::misc::QBM_API_BASE;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use util::{BenchmarkInfo, NodeConstraints, NodeInfo};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterRole {
+    /// Runs tasks itself, the default.
+    Standalone,
+    /// Forwards tasks to registered workers.
+    Coordinator,
+    /// Runs tasks and announces itself to a coordinator.
+    Worker,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ClusterConfig {
+    pub role: ClusterRole,
+    /// Coordinator a worker sends heartbeats to.
+    pub coordinator: Option<String>,
+    /// Address a worker announces, it has to be reachable by the coordinator.
+    pub advertise: Option<String>,
+    pub heartbeat_secs: u64,
+    /// Workers without a heartbeat for this long are no longer dispatched to.
+    pub node_timeout_secs: u64,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        ClusterConfig {
+            role: ClusterRole::Standalone,
+            coordinator: None,
+            advertise: None,
+            heartbeat_secs: 10,
+            node_timeout_secs: 30,
+        }
+    }
+}
+
+struct Node {
+    info: NodeInfo,
+    last_seen: Instant,
+    running: usize,
+}
+
+/// Workers known to a coordinator, keyed by address.
+pub struct NodeRegistry {
+    timeout: Duration,
+    nodes: Mutex<HashMap<String, Node>>,
+}
+
+impl NodeRegistry {
+    pub fn new(timeout: Duration) -> Arc<NodeRegistry> {
+        Arc::new(NodeRegistry {
+            timeout,
+            nodes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn heartbeat(&self, info: NodeInfo) {
+        let mut nodes = self.nodes.lock().unwrap();
+
+        match nodes.get_mut(&info.address) {
+            Some(node) => {
+                node.info = info;
+                node.last_seen = Instant::now();
+            }
+            None => {
+                info!("node {} joined", info.address);
+
+                nodes.insert(
+                    info.address.clone(),
+                    Node {
+                        info,
+                        last_seen: Instant::now(),
+                        running: 0,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Workers that sent a heartbeat recently, ordered by address.
+    pub fn nodes(&self) -> Vec<NodeInfo> {
+        let nodes = self.nodes.lock().unwrap();
+
+        let mut alive = nodes
+            .values()
+            .filter(|n| n.last_seen.elapsed() < self.timeout)
+            .map(|n| n.info.clone())
+            .collect::<Vec<_>>();
+        alive.sort_by(|a, b| a.address.cmp(&b.address));
+
+        alive
+    }
+
+    /// Reserves the least busy live worker that has the compiler and matches
+    /// `constraints`, the reservation ends when the `Dispatch` is dropped.
+    pub fn select(
+        self: &Arc<Self>,
+        compiler: &str,
+        compiler_version: &str,
+        constraints: &NodeConstraints,
+    ) -> Option<Dispatch> {
+        let mut nodes = self.nodes.lock().unwrap();
+
+        let node = nodes
+            .values_mut()
+            .filter(|n| n.last_seen.elapsed() < self.timeout)
+            .filter(|n| {
+                n.info
+                    .compilers
+                    .iter()
+                    .any(|c| c.name == compiler && c.version == compiler_version)
+            })
+            .filter(|n| constraints.matches(&n.info))
+            .min_by(|a, b| {
+                a.running
+                    .cmp(&b.running)
+                    .then_with(|| a.info.address.cmp(&b.info.address))
+            })?;
+
+        node.running += 1;
+
+        Some(Dispatch {
+            registry: self.clone(),
+            node: node.info.clone(),
+        })
+    }
+}
+
+pub struct Dispatch {
+    registry: Arc<NodeRegistry>,
+    pub node: NodeInfo,
+}
+
+impl Dispatch {
+    /// Posts `task` to the worker's `/run` and waits for its result.
+    pub fn run(&self, task: &serde_json::Value) -> Result<BenchmarkInfo, String> {
+        let url = format!("{}/run", api_url(&self.node.address));
+        let response = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(5))
+            .build()
+            .post(&url)
+            .send_json(task);
+
+        let response = match response {
+            Ok(response) => response,
+            // Rejections carry a `BenchmarkInfo` explaining them.
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        response
+            .into_json()
+            .map_err(|e| format!("invalid response from {}, {}", self.node.address, e))
+    }
+}
+
+impl Drop for Dispatch {
+    fn drop(&mut self) {
+        let mut nodes = self.registry.nodes.lock().unwrap();
+
+        if let Some(node) = nodes.get_mut(&self.node.address) {
+            node.running = node.running.saturating_sub(1);
+        }
+    }
+}
+
+/// Announces `info` to the coordinator, called periodically by workers. The
+/// coordinator only accepts workers sharing its `secrets`.
+pub fn send_heartbeat(coordinator: &str, secrets: &str, info: &NodeInfo) -> Result<(), String> {
+    ureq::post(&format!("{}/nodes/heartbeat", api_url(coordinator)))
+        .timeout(Duration::from_secs(5))
+        .set("Authorization", &format!("Bearer {}", secrets))
+        .send_json(info)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Forwards a cancellation to a worker, `false` if it does not run the task.
+pub fn send_cancel(address: &str, secrets: &str, task_uid: &str) -> Result<bool, String> {
+    let response = ureq::post(&format!("{}/tasks/{}/cancel", api_url(address), task_uid))
+        .timeout(Duration::from_secs(5))
+        .set("Authorization", &format!("Bearer {}", secrets))
+        .call();
+
+    match response {
+        Ok(_) => Ok(true),
+        Err(ureq::Error::Status(404, _)) => Ok(false),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn api_url(address: &str) -> String {
+    let address = address.trim_end_matches('/');
+
+    if address.starts_with("http://") || address.starts_with("https://") {
+        format!("{}{}", address, QBM_API_BASE)
+    } else {
+        format!("http://{}{}", address, QBM_API_BASE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::{CompilerInfo, RemoteServerInfo};
+
+    fn node(address: &str, cpu_vendor: &str, compiler: &str) -> NodeInfo {
+        NodeInfo {
+            address: address.to_string(),
+            environment: RemoteServerInfo {
+                platform: "Linux".to_string(),
+                kernel_version: "5.14.0".to_string(),
+                os_version: "Linux".to_string(),
+                total_memory: "16.00 GB".to_string(),
+                total_swap: "0.00 GB".to_string(),
+                cpu_cores: 8,
+                cpu_logical_cores: 16,
+                cpu_vendor: cpu_vendor.to_string(),
+                cpu_brand: format!("{} CPU", cpu_vendor),
+                cpus: vec![],
+                readiness: vec![],
+            },
+            compilers: vec![CompilerInfo {
+                name: compiler.to_string(),
+                version: "11.2.0".to_string(),
+                target: None,
+                thread_model: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn selects_matching_nodes() {
+        let registry = NodeRegistry::new(Duration::from_secs(30));
+
+        registry.heartbeat(node("a:9000", "GenuineIntel", "g++"));
+        registry.heartbeat(node("b:9000", "AuthenticAMD", "g++"));
+        registry.heartbeat(node("c:9000", "AuthenticAMD", "clang++"));
+
+        let amd = NodeConstraints {
+            cpu_vendor: Some("amd".to_string()),
+            ..Default::default()
+        };
+        let dispatch = registry.select("g++", "11.2.0", &amd).unwrap();
+        assert_eq!(dispatch.node.address, "b:9000");
+
+        let pinned = NodeConstraints {
+            node: Some("a:9000".to_string()),
+            ..Default::default()
+        };
+        let dispatch = registry.select("g++", "11.2.0", &pinned).unwrap();
+        assert_eq!(dispatch.node.address, "a:9000");
+
+        let arm = NodeConstraints {
+            cpu_vendor: Some("arm".to_string()),
+            ..Default::default()
+        };
+        assert!(registry.select("g++", "11.2.0", &arm).is_none());
+        assert!(registry
+            .select("g++", "9.4.0", &NodeConstraints::default())
+            .is_none());
+    }
+
+    #[test]
+    fn balances_running_tasks() {
+        let registry = NodeRegistry::new(Duration::from_secs(30));
+
+        registry.heartbeat(node("a:9000", "GenuineIntel", "g++"));
+        registry.heartbeat(node("b:9000", "GenuineIntel", "g++"));
+
+        let any = NodeConstraints::default();
+        let first = registry.select("g++", "11.2.0", &any).unwrap();
+        let second = registry.select("g++", "11.2.0", &any).unwrap();
+        assert_eq!(first.node.address, "a:9000");
+        assert_eq!(second.node.address, "b:9000");
+
+        drop(first);
+        let third = registry.select("g++", "11.2.0", &any).unwrap();
+        assert_eq!(third.node.address, "a:9000");
+    }
+
+    #[test]
+    fn forgets_silent_nodes() {
+        let registry = NodeRegistry::new(Duration::from_millis(0));
+
+        registry.heartbeat(node("a:9000", "GenuineIntel", "g++"));
+
+        assert!(registry.nodes().is_empty());
+        assert!(registry
+            .select("g++", "11.2.0", &NodeConstraints::default())
+            .is_none());
+    }
+}
@@ -1,30 +1,72 @@
+use crate::baseline::RegressionPolicy;
+use crate::cluster::{ClusterConfig, ClusterRole};
 use crate::env::collect_system_compiler_entry;
-use crate::misc::QBM_DEFAULT_CONFIG_FILE_PATH;
-use cached::proc_macro::cached;
+use crate::misc::{QBM_DEFAULT_CONFIG_FILE_PATH, QBM_DEFAULT_DATA_DIR};
 use clap::{App, Arg};
 use figment::providers::{Env, Format, Serialized, Toml};
-use figment::value::{Dict, Value};
+use figment::value::Value;
 use figment::{map, Figment};
+use notify::{RecursiveMode, Watcher};
+use qbmr::{
+    config_errors, default_iteration_timeout_secs, validate_compilers, CompilerConfig,
+    ReadinessPolicy, SYSTEM_COMPILER_COMMAND, SYSTEM_COMPILER_EXTENSIONS,
+};
 use serde::{Deserialize, Serialize};
-use toml::value::Table;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use util::ArgPolicy;
 
-#[derive(Serialize, Deserialize)]
-pub struct ConfigFile {
-    port: Option<u16>,
-    secrets: String,
-    compilers: Option<Table>,
-}
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(200);
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct CompilerEntry {
     pub name: String,
     pub path: String,
     pub version: String,
+    /// Same placeholders as in qbmr.toml, compilers found on the `PATH` are
+    /// run like gcc.
+    #[serde(default = "default_compiler_command")]
+    pub command: String,
+    #[serde(default = "default_compiler_extensions")]
+    pub extensions: Vec<String>,
+}
+
+fn default_compiler_command() -> String {
+    SYSTEM_COMPILER_COMMAND.to_string()
+}
+
+fn default_compiler_extensions() -> Vec<String> {
+    SYSTEM_COMPILER_EXTENSIONS
+        .iter()
+        .map(|e| e.to_string())
+        .collect()
+}
+
+impl CompilerEntry {
+    /// The entry as the qbmr engine takes it.
+    pub fn config(&self) -> CompilerConfig {
+        CompilerConfig {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            version: self.version.clone(),
+            command: self.command.clone(),
+            extensions: self.extensions.clone(),
+        }
+    }
 }
 
 impl From<CompilerEntry> for figment::value::Value {
     fn from(entry: CompilerEntry) -> Self {
-        Value::from(map!["name" => entry.name, "path" => entry.path, "version" => entry.version ])
+        Value::from(map![
+            "name" => Value::from(entry.name),
+            "path" => Value::from(entry.path),
+            "version" => Value::from(entry.version),
+            "command" => Value::from(entry.command),
+            "extensions" => Value::from(entry.extensions)
+        ])
     }
 }
 
@@ -32,10 +74,103 @@ impl From<CompilerEntry> for figment::value::Value {
 pub struct Config {
     pub secrets: String,
     pub compilers: Vec<CompilerEntry>,
+    #[serde(default)]
+    pub compiler_args: ArgPolicy,
+    pub data_dir: String,
+    #[serde(default)]
+    pub regression: RegressionPolicy,
+    #[serde(default)]
+    pub readiness: ReadinessPolicy,
+    /// Runs of a program taking longer are killed.
+    #[serde(default = "default_iteration_timeout_secs")]
+    pub iteration_timeout_secs: u64,
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+}
+
+impl Config {
+    /// Problems that would otherwise only show up when a task runs, each
+    /// prefixed with the TOML key it is about.
+    pub fn validate(&self) -> Vec<String> {
+        let compilers = self
+            .compilers
+            .iter()
+            .map(CompilerEntry::config)
+            .collect::<Vec<_>>();
+        let mut errors = validate_compilers(&compilers);
+
+        if self.data_dir.trim().is_empty() {
+            errors.push("data_dir: must not be empty".to_string());
+        }
+
+        let regression = &self.regression;
+
+        if regression.threshold < 0.0 || !regression.threshold.is_finite() {
+            errors.push(format!(
+                "regression.threshold: {} is not a tolerated slowdown, use e.g. 0.05 for 5%",
+                regression.threshold
+            ));
+        }
+
+        if !(regression.significance > 0.0 && regression.significance < 1.0) {
+            errors.push(format!(
+                "regression.significance: {} is not between 0 and 1",
+                regression.significance
+            ));
+        }
+
+        if let Some(max_load) = self.readiness.max_load.filter(|l| l.is_nan() || *l < 0.0) {
+            errors.push(format!(
+                "readiness.max_load: {} is not a load average",
+                max_load
+            ));
+        }
+
+        if self.iteration_timeout_secs == 0 {
+            errors.push("iteration_timeout_secs: must be at least 1".to_string());
+        }
+
+        let cluster = &self.cluster;
+
+        if cluster.role == ClusterRole::Worker {
+            if cluster.coordinator.is_none() {
+                errors.push(
+                    "cluster.coordinator: workers need the address of their coordinator"
+                        .to_string(),
+                );
+            }
+
+            if cluster.advertise.is_none() {
+                errors.push(
+                    "cluster.advertise: workers need the address the coordinator reaches them at"
+                        .to_string(),
+                );
+            }
+        }
+
+        if cluster.heartbeat_secs == 0 {
+            errors.push("cluster.heartbeat_secs: must be at least 1".to_string());
+        }
+
+        if cluster.node_timeout_secs <= cluster.heartbeat_secs {
+            errors.push(format!(
+                "cluster.node_timeout_secs: must be longer than cluster.heartbeat_secs ({})",
+                cluster.heartbeat_secs
+            ));
+        }
+
+        errors
+    }
+}
+
+pub struct ServerArgs {
+    pub config_path: String,
+    /// Only validate the config and exit.
+    pub check_config: bool,
 }
 
-#[cached(size = 1, time = 15)]
-pub fn setup_config() -> Figment {
+/// Command line arguments, they are parsed once at startup.
+pub fn server_args() -> ServerArgs {
     let matches = App::new("Quick Benchmark")
         .version("0.1.0")
         .author("Twiliness <https://github.com/DarkHighness>")
@@ -48,6 +183,11 @@ pub fn setup_config() -> Figment {
                 .help("Sets a custom config file")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("check-config")
+                .long("check-config")
+                .help("Validates the config file and exits"),
+        )
         .get_matches();
 
     let config_file_path = if matches.value_of("config").is_none() {
@@ -63,7 +203,31 @@ pub fn setup_config() -> Figment {
 
     info!("Config path: {}", config_file_path);
 
-    let map = map!["port" => Value::from(9000), "compilers" => Value::from(collect_system_compiler_entry())];
+    ServerArgs {
+        config_path: config_file_path.to_string(),
+        check_config: matches.is_present("check-config"),
+    }
+}
+
+/// The config in `config_file_path` if it is valid, otherwise every problem
+/// found in it.
+pub fn check_config(config_file_path: &str) -> Result<Config, Vec<String>> {
+    let config = load_config(config_file_path)
+        .extract::<Config>()
+        .map_err(config_errors)?;
+
+    match config.validate() {
+        errors if errors.is_empty() => Ok(config),
+        errors => Err(errors),
+    }
+}
+
+pub fn load_config(config_file_path: &str) -> Figment {
+    let map = map![
+        "port" => Value::from(9000),
+        "data_dir" => Value::from(QBM_DEFAULT_DATA_DIR),
+        "compilers" => Value::from(collect_system_compiler_entry())
+    ];
 
     Figment::new()
         .merge(Serialized::from(&map, "default"))
@@ -71,3 +235,219 @@ pub fn setup_config() -> Figment {
         .merge(Env::prefixed("QBMS_"))
         .join(rocket::Config::figment())
 }
+
+/// The running config. A reload swaps it as a whole, requests holding the
+/// previous one finish with it.
+pub struct ConfigStore {
+    path: String,
+    current: RwLock<Arc<Config>>,
+}
+
+impl ConfigStore {
+    pub fn open(path: &str) -> Result<ConfigStore, String> {
+        let config = check_config(path)
+            .map_err(|errors| format!("invalid config {}:\n  {}", path, errors.join("\n  ")))?;
+
+        Ok(ConfigStore {
+            path: path.to_string(),
+            current: RwLock::new(Arc::new(config)),
+        })
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Reads the config file again, an invalid file is reported and the
+    /// running config kept. Settings only read at startup keep their running
+    /// values until a restart.
+    pub fn reload(&self) -> Result<Arc<Config>, String> {
+        let mut config = check_config(&self.path).map_err(|errors| {
+            format!(
+                "invalid config {}, keeping the running one:\n  {}",
+                self.path,
+                errors.join("\n  ")
+            )
+        })?;
+
+        let mut current = self.current.write().unwrap();
+
+        for key in restart_required(&current, &config) {
+            warn!("{} changed, it takes effect after a restart", key);
+        }
+
+        config.data_dir = current.data_dir.clone();
+        config.cluster = current.cluster.clone();
+
+        let config = Arc::new(config);
+        *current = config.clone();
+
+        Ok(config)
+    }
+}
+
+/// Settings that are only read at startup.
+fn restart_required(previous: &Config, config: &Config) -> Vec<&'static str> {
+    let mut keys = vec![];
+
+    if previous.data_dir != config.data_dir {
+        keys.push("data_dir");
+    }
+
+    if serde_json::to_value(&previous.cluster).ok() != serde_json::to_value(&config.cluster).ok() {
+        keys.push("cluster");
+    }
+
+    keys
+}
+
+/// Reloads `store` whenever its file changes, for as long as the server runs.
+pub fn watch_config(store: Arc<ConfigStore>) -> Result<(), String> {
+    let path = Path::new(&store.path);
+    // Editors often save by replacing the file, so its directory is watched.
+    let directory = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("invalid config path {}", store.path))?
+        .to_owned();
+
+    let (sender, receiver) = mpsc::channel();
+
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if event.kind.is_access() => {}
+            Ok(event) => {
+                if event
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == Some(&file_name))
+                {
+                    let _ = sender.send(());
+                }
+            }
+            Err(e) => warn!("config watch failed: {}", e),
+        })
+        .map_err(|e| format!("unable to watch {}, {}", store.path, e))?;
+
+    watcher
+        .watch(directory, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("unable to watch {}, {}", store.path, e))?;
+
+    thread::spawn(move || {
+        let _watcher = watcher;
+
+        while receiver.recv().is_ok() {
+            // A save is often several events, reload once they stop.
+            while receiver.recv_timeout(RELOAD_DEBOUNCE).is_ok() {}
+
+            match store.reload() {
+                Ok(_) => info!("reloaded {}", store.path),
+                Err(e) => error!("{}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn keeps_running_config_when_reload_fails() {
+        let path = env::temp_dir().join(format!("qbms-conf-{}.toml", std::process::id()));
+        let compilers = "[[compilers]]\nname = \"g++\"\npath = \"/bin/sh\"\nversion = \"11.2.0\"\n";
+        fs::write(&path, format!("secrets = \"x\"\n{}", compilers)).unwrap();
+
+        let store = ConfigStore::open(path.to_str().unwrap()).unwrap();
+        assert_eq!(store.current().compilers[0].version, "11.2.0");
+
+        fs::write(&path, "secrets = [").unwrap();
+        assert!(store.reload().is_err());
+        assert_eq!(store.current().compilers[0].version, "11.2.0");
+
+        fs::write(
+            &path,
+            format!("secrets = \"x\"\n{}", compilers.replace("11.2.0", "12.1.0")),
+        )
+        .unwrap();
+        store.reload().unwrap();
+        assert_eq!(store.current().compilers[0].version, "12.1.0");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keeps_startup_settings_on_reload() {
+        let path = env::temp_dir().join(format!("qbms-restart-{}.toml", std::process::id()));
+        let compilers = "[[compilers]]\nname = \"g++\"\npath = \"/bin/sh\"\nversion = \"11.2.0\"\n";
+        fs::write(
+            &path,
+            format!("secrets = \"x\"\ndata_dir = \"a\"\n{}", compilers),
+        )
+        .unwrap();
+
+        let store = ConfigStore::open(path.to_str().unwrap()).unwrap();
+
+        fs::write(
+            &path,
+            format!(
+                "secrets = \"y\"\ndata_dir = \"b\"\n{}\n[cluster]\nrole = \"coordinator\"\n",
+                compilers
+            ),
+        )
+        .unwrap();
+        store.reload().unwrap();
+
+        let config = store.current();
+        assert_eq!(config.secrets, "y");
+        assert_eq!(config.data_dir, "a");
+        assert!(config.cluster.role == ClusterRole::Standalone);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reports_offending_keys() {
+        let path = env::temp_dir().join(format!("qbms-check-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            r#"
+            secrets = "x"
+
+            [[compilers]]
+            name = "g++"
+            path = "/qbm/no/such/g++"
+            version = "11.2.0"
+
+            [regression]
+            significance = 2.0
+
+            [cluster]
+            role = "worker"
+            coordinator = "10.0.0.1:9000"
+            "#,
+        )
+        .unwrap();
+
+        let errors = check_config(path.to_str().unwrap()).err().unwrap();
+
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[0].starts_with("compilers[0].path: "));
+        assert!(errors[1].starts_with("regression.significance: "));
+        assert!(errors[2].starts_with("cluster.advertise: "));
+
+        fs::write(&path, "secrets = \"x\"\ncompilers = \"g++\"\n").unwrap();
+        let errors = check_config(path.to_str().unwrap()).err().unwrap();
+
+        assert!(errors[0].starts_with("compilers: "), "{:?}", errors);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
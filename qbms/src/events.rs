@@ -0,0 +1,318 @@
+use qbmr::RunningProgram;
+use rocket::tokio::sync::broadcast;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use util::{ActiveTask, TaskEvent, TaskState};
+
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+struct TaskChannel {
+    history: Vec<TaskEvent>,
+    sender: broadcast::Sender<TaskEvent>,
+    cancelled: bool,
+    program: Option<RunningProgram>,
+    created_at: Instant,
+}
+
+impl TaskChannel {
+    fn new() -> TaskChannel {
+        TaskChannel {
+            history: vec![],
+            sender: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            cancelled: false,
+            program: None,
+            created_at: Instant::now(),
+        }
+    }
+}
+
+/// Fans out the events of running tasks to `GET /tasks/<task_uid>/events`.
+///
+/// State events are kept until the task is done, so a subscriber that
+/// connects late still sees how far it got. Output is only sent live and of
+/// consecutive iterations only the latest is kept, which bounds the history
+/// however long a task runs. Finished tasks are served from the result store
+/// instead.
+#[derive(Default)]
+pub struct EventHub {
+    tasks: Mutex<HashMap<String, TaskChannel>>,
+}
+
+impl EventHub {
+    pub fn publish(&self, task_uid: &str, event: TaskEvent) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let done = matches!(event, TaskEvent::Done { .. });
+
+        let channel = tasks
+            .entry(task_uid.to_string())
+            .or_insert_with(TaskChannel::new);
+
+        // No receivers is not an error, the event is still kept in history.
+        let _ = channel.sender.send(event.clone());
+
+        match event {
+            TaskEvent::Output { .. } => {}
+            TaskEvent::Iteration { .. }
+                if matches!(channel.history.last(), Some(TaskEvent::Iteration { .. })) =>
+            {
+                *channel.history.last_mut().unwrap() = event;
+            }
+            event => channel.history.push(event),
+        }
+
+        if done {
+            tasks.remove(task_uid);
+        }
+    }
+
+    /// Events published so far plus a receiver for the rest of them.
+    ///
+    /// Subscribing to a task that has not started yet is allowed.
+    pub fn subscribe(self: &Arc<Self>, task_uid: &str) -> Subscription {
+        let mut tasks = self.tasks.lock().unwrap();
+
+        let channel = tasks
+            .entry(task_uid.to_string())
+            .or_insert_with(TaskChannel::new);
+
+        Subscription {
+            hub: self.clone(),
+            task_uid: task_uid.to_string(),
+            history: channel.history.clone(),
+            receiver: channel.sender.subscribe(),
+        }
+    }
+
+    pub fn is_running(&self, task_uid: &str) -> bool {
+        self.tasks
+            .lock()
+            .unwrap()
+            .get(task_uid)
+            .map(|c| !c.history.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Queued and running tasks, oldest first.
+    pub fn active(&self) -> Vec<ActiveTask> {
+        let tasks = self.tasks.lock().unwrap();
+
+        let mut active = tasks
+            .iter()
+            .filter(|(_, c)| !c.history.is_empty())
+            .map(|(task_uid, c)| {
+                let mut task = ActiveTask {
+                    task_uid: task_uid.clone(),
+                    state: TaskState::Queued,
+                    iteration: 0,
+                    iterations: 0,
+                };
+
+                for event in &c.history {
+                    match event {
+                        TaskEvent::Compiling => task.state = TaskState::Compiling,
+                        TaskEvent::Compiled { .. } => task.state = TaskState::Running,
+                        TaskEvent::Iteration {
+                            iteration,
+                            iterations,
+                            ..
+                        } => {
+                            task.iteration = *iteration;
+                            task.iterations = *iterations;
+                        }
+                        _ => {}
+                    }
+                }
+
+                (c.created_at, task)
+            })
+            .collect::<Vec<_>>();
+        active.sort_by_key(|(created_at, _)| *created_at);
+
+        active.into_iter().map(|(_, task)| task).collect()
+    }
+
+    /// Remembers the program a task is running so `cancel` can kill it.
+    pub fn started(&self, task_uid: &str, program: &RunningProgram) {
+        let mut tasks = self.tasks.lock().unwrap();
+
+        if let Some(channel) = tasks.get_mut(task_uid) {
+            // The task may have been cancelled while the program started.
+            if channel.cancelled {
+                program.kill();
+            }
+
+            channel.program = Some(program.clone());
+        }
+    }
+
+    /// Stops a queued or running task, its program is killed and it finishes
+    /// with an error.
+    pub fn cancel(&self, task_uid: &str) -> bool {
+        match self.tasks.lock().unwrap().get_mut(task_uid) {
+            Some(channel) if !channel.history.is_empty() => {
+                channel.cancelled = true;
+
+                if let Some(program) = &channel.program {
+                    program.kill();
+                }
+
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_cancelled(&self, task_uid: &str) -> bool {
+        self.tasks
+            .lock()
+            .unwrap()
+            .get(task_uid)
+            .is_some_and(|c| c.cancelled)
+    }
+}
+
+pub struct Subscription {
+    hub: Arc<EventHub>,
+    task_uid: String,
+    pub history: Vec<TaskEvent>,
+    pub receiver: broadcast::Receiver<TaskEvent>,
+}
+
+impl Drop for Subscription {
+    /// Drops the channel of a task that never started once its last
+    /// subscriber is gone, `self.receiver` is still counted at this point.
+    fn drop(&mut self) {
+        let mut tasks = self.hub.tasks.lock().unwrap();
+
+        let unused = tasks
+            .get(&self.task_uid)
+            .map(|c| c.history.is_empty() && c.sender.receiver_count() <= 1)
+            .unwrap_or(false);
+
+        if unused {
+            tasks.remove(&self.task_uid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::BenchmarkInfo;
+
+    fn done(task_uid: &str) -> TaskEvent {
+        TaskEvent::Done {
+            result: Box::new(BenchmarkInfo::new(task_uid)),
+        }
+    }
+
+    #[test]
+    fn replays_history_to_late_subscribers() {
+        let hub = Arc::new(EventHub::default());
+
+        hub.publish("task", TaskEvent::Queued);
+        hub.publish("task", TaskEvent::Compiling);
+
+        let mut subscription = hub.subscribe("task");
+        assert_eq!(subscription.history.len(), 2);
+        assert!(hub.is_running("task"));
+
+        hub.publish("task", done("task"));
+
+        assert!(matches!(
+            subscription.receiver.try_recv(),
+            Ok(TaskEvent::Done { .. })
+        ));
+        assert!(!hub.is_running("task"));
+    }
+
+    #[test]
+    fn keeps_only_the_latest_progress() {
+        let hub = Arc::new(EventHub::default());
+
+        hub.publish("task", TaskEvent::Queued);
+
+        for iteration in 1..=1000 {
+            hub.publish(
+                "task",
+                TaskEvent::Output {
+                    iteration,
+                    output: "x".repeat(1000),
+                },
+            );
+            hub.publish(
+                "task",
+                TaskEvent::Iteration {
+                    iteration,
+                    iterations: 1000,
+                    elapsed_ns: 100,
+                },
+            );
+        }
+
+        let subscription = hub.subscribe("task");
+        assert_eq!(subscription.history.len(), 2);
+        assert!(matches!(
+            subscription.history[1],
+            TaskEvent::Iteration {
+                iteration: 1000,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn lists_active_tasks() {
+        let hub = Arc::new(EventHub::default());
+
+        hub.publish("first", TaskEvent::Queued);
+        hub.publish("first", TaskEvent::Compiling);
+        hub.publish(
+            "first",
+            TaskEvent::Compiled {
+                output: "".to_string(),
+            },
+        );
+        hub.publish(
+            "first",
+            TaskEvent::Iteration {
+                iteration: 2,
+                iterations: 5,
+                elapsed_ns: 100,
+            },
+        );
+        hub.publish("second", TaskEvent::Queued);
+        let _subscription = hub.subscribe("not-submitted");
+
+        let active = hub.active();
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].task_uid, "first");
+        assert_eq!(active[0].state, TaskState::Running);
+        assert_eq!((active[0].iteration, active[0].iterations), (2, 5));
+        assert_eq!(active[1].state, TaskState::Queued);
+    }
+
+    #[test]
+    fn cancels_running_tasks() {
+        let hub = Arc::new(EventHub::default());
+
+        assert!(!hub.cancel("task"));
+
+        hub.publish("task", TaskEvent::Queued);
+        assert!(hub.cancel("task"));
+        assert!(hub.is_cancelled("task"));
+
+        hub.publish("task", done("task"));
+        assert!(!hub.is_cancelled("task"));
+    }
+
+    #[test]
+    fn forgets_tasks_nobody_runs() {
+        let hub = Arc::new(EventHub::default());
+
+        drop(hub.subscribe("never-run"));
+
+        assert!(hub.tasks.lock().unwrap().is_empty());
+    }
+}
@@ -1,51 +1,147 @@
-use path_absolutize::*;
-use std::fs;
-use std::ops::Not;
-use std::path::Path;
-use std::process::Command;
-
-pub fn compile_file(
-    compiler_path: &str,
-    compiler_args: &str,
-    source_code: &str,
-    file_name: &str,
-    file_ext: &str,
-) -> Result<String, String> {
-    let cache_dir = Path::new("./cache/");
-
-    if cache_dir.exists().not() {
-        fs::create_dir(cache_dir);
+use crate::events::EventHub;
+use qbmr::{BenchmarkObserver, Engine, RunningProgram};
+use util::{BenchmarkInfo, BenchmarkTask, TaskEvent};
+
+/// Publishes the progress of a task to the event hub.
+struct HubObserver<'a> {
+    hub: &'a EventHub,
+    task_uid: &'a str,
+    iterations: u32,
+}
+
+impl BenchmarkObserver for HubObserver<'_> {
+    fn compiling(&mut self) {
+        self.hub.publish(self.task_uid, TaskEvent::Compiling);
+    }
+
+    fn compiled(&mut self, output: &str) {
+        self.hub.publish(
+            self.task_uid,
+            TaskEvent::Compiled {
+                output: output.to_string(),
+            },
+        );
+    }
+
+    fn started(&mut self, _iteration: u32, program: &RunningProgram) {
+        self.hub.started(self.task_uid, program);
+    }
+
+    fn iteration(&mut self, iteration: u32, elapsed_ns: u64) {
+        self.hub.publish(
+            self.task_uid,
+            TaskEvent::Iteration {
+                iteration,
+                iterations: self.iterations,
+                elapsed_ns,
+            },
+        );
+    }
+
+    fn output(&mut self, iteration: u32, output: &str) {
+        self.hub.publish(
+            self.task_uid,
+            TaskEvent::Output {
+                iteration,
+                output: output.to_string(),
+            },
+        );
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.hub.is_cancelled(self.task_uid)
     }
+}
 
-    let source_file_name = format!("{}.{}", file_name, file_ext);
-    let source_file_path = cache_dir.to_path_buf().join(&source_file_name);
-
-    fs::write(&source_file_path, source_code);
-
-    let output = if cfg!(target_os = "windows") {
-        Command::new("powershell")
-            .current_dir(cache_dir.absolutize().unwrap())
-            .args([
-                "-Command",
-                &format!("&{{{} {} -o {}}}", compiler_path, compiler_args, file_name),
-            ])
-            .output()
-    } else {
-        Command::new(compiler_path)
-            .current_dir(cache_dir.absolutize().unwrap())
-            .args([compiler_args, &source_file_name, "-o", file_name])
-            .output()
+/// Compiles and runs a task with `engine`, publishing its progress to `hub`.
+pub fn execute_task(hub: &EventHub, engine: &Engine, task: &BenchmarkTask) -> BenchmarkInfo {
+    let observer = HubObserver {
+        hub,
+        task_uid: &task.task_uid,
+        iterations: task.iterations,
     };
 
-    if output.is_err() {
-        Err(output.err().unwrap().to_string())
-    } else {
-        let output = output.unwrap();
+    engine.benchmark(task, observer)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use qbmr::{CompilerConfig, TASK_CANCELLED};
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use util::{ArgPolicy, NodeConstraints, TaskState, QBM_PROTOCOL_VERSION};
+
+    const TASK_UID: &str = "0123456789abcdef";
+
+    /// "Compiles" a shell script by copying it to the `-o` target.
+    const STUB_COMPILER: &str = "#!/bin/sh\ncp \"$1\" \"$3\" && chmod +x \"$3\"\n";
+
+    #[test]
+    fn cancel_kills_the_running_program() {
+        let root = env::temp_dir().join(format!("qbms-execution-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        let compiler = root.join("stubcc");
+        fs::write(&compiler, STUB_COMPILER).unwrap();
+        fs::set_permissions(&compiler, fs::Permissions::from_mode(0o755)).unwrap();
 
-        if output.status.success().not() {
-            Err(String::from_utf8(output.stderr).unwrap())
-        } else {
-            Ok(String::from_utf8(output.stdout).unwrap())
+        let engine = Engine::new(
+            &[CompilerConfig {
+                name: "stub".to_string(),
+                path: compiler.to_str().unwrap().to_string(),
+                version: "1.0.0".to_string(),
+                command: "${COMPILER} ${SOURCE_FILES} -o ${TARGET_FILE_NAME}".to_string(),
+                extensions: vec!["sh".to_string()],
+            }],
+            &ArgPolicy::default(),
+            root.join("source").to_str().unwrap(),
+            root.join("build").to_str().unwrap(),
+        )
+        .unwrap();
+        let task = BenchmarkTask {
+            protocol_version: QBM_PROTOCOL_VERSION,
+            task_uid: TASK_UID.to_string(),
+            compiler: "stub".to_string(),
+            compiler_version: "1.0.0".to_string(),
+            source_file: "main.sh".to_string(),
+            source_code: "#!/bin/sh\nexec sleep 30\n".to_string(),
+            compiler_args: "".to_string(),
+            assembly: false,
+            iterations: 3,
+            name: None,
+            user: None,
+            tags: vec![],
+            constraints: NodeConstraints::default(),
+        };
+
+        let hub = Arc::new(EventHub::default());
+        hub.publish(TASK_UID, TaskEvent::Queued);
+
+        let start = Instant::now();
+        let running = {
+            let hub = hub.clone();
+            thread::spawn(move || execute_task(&hub, &engine, &task))
+        };
+
+        while !hub.active().iter().any(|t| t.state == TaskState::Running) {
+            assert!(start.elapsed() < Duration::from_secs(10), "never started");
+            thread::sleep(Duration::from_millis(10));
         }
+        thread::sleep(Duration::from_millis(100));
+        assert!(hub.cancel(TASK_UID));
+
+        let result = running.join().unwrap();
+
+        assert_eq!(result.message, TASK_CANCELLED);
+        assert!(result.timings.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        fs::remove_dir_all(&root).unwrap();
     }
 }
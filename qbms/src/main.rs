@@ -1,103 +1,685 @@
-use crate::conf::{setup_config, Config};
-use crate::env::collect_system_compiler_info;
-use crate::execution::compile_file;
-use env::collect_env_info;
-use figment::Figment;
-use rocket::futures::AsyncWriteExt;
+use crate::auth::BearerToken;
+use crate::cluster::{send_cancel, send_heartbeat, ClusterRole, NodeRegistry};
+use crate::conf::{check_config, load_config, server_args, watch_config, Config, ConfigStore};
+use crate::env::{collect_env_info, configured_compiler_info};
+use crate::events::EventHub;
+use crate::execution::execute_task;
+use crate::misc::{QBM_API_BASE, QBM_MAX_ITERATIONS};
+use crate::store::{task_record, HistoryQuery, ResultStore};
+use qbmr::{collect_host_state, Engine, TASK_CANCELLED};
+use rocket::fairing::AdHoc;
+use rocket::http::{ContentType, Status};
+use rocket::response::content::{Css, Html, JavaScript};
+use rocket::response::stream::{Event, EventStream};
 use rocket::serde::json::Json;
-use rocket::{Build, Rocket};
-use std::borrow::{Borrow, BorrowMut};
-use std::cell::RefCell;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::tokio::sync::Semaphore;
+use rocket::tokio::task::spawn_blocking;
+use rocket::tokio::time::sleep;
+use rocket::{Route, Shutdown, State};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use std::net::SocketAddr;
 use std::path::Path;
-use std::sync::atomic::AtomicPtr;
+use std::process;
 use std::sync::Arc;
-use std::sync::Mutex;
-use util::{BenchmarkInfo, BenchmarkTask, CompilerInfo, RemoteServerInfo};
+use std::time::Duration;
+use util::{
+    histogram_svg, html_report, split_args, validate_file_name, validate_task_uid, ActiveTask,
+    BenchmarkInfo, BenchmarkTask, CompilerInfo, Handshake, NodeInfo, RemoteServerInfo, TaskEvent,
+    TaskRecord, QBM_MIN_PROTOCOL_VERSION, QBM_PROTOCOL_VERSION,
+};
 
+mod auth;
+mod baseline;
+mod cluster;
 mod conf;
 mod env;
+mod events;
 mod execution;
 mod misc;
+mod openapi;
+mod store;
 
 #[macro_use]
 extern crate rocket;
-#[macro_use]
-extern crate lazy_static;
+
+type TaskResponse = (Status, Json<BenchmarkInfo>);
+
+fn task_response(task_uid: &str, status: Status, message: String) -> TaskResponse {
+    (status, Json(BenchmarkInfo::failed(task_uid, message)))
+}
+
+// The web front end is compiled into the binary.
 
 #[get("/")]
-fn index() -> &'static str {
-    "QBM v0.1.0"
+fn index() -> Html<&'static str> {
+    Html(include_str!("../static/index.html"))
+}
+
+#[get("/static/app.js")]
+fn script() -> JavaScript<&'static str> {
+    JavaScript(include_str!("../static/app.js"))
+}
+
+#[get("/static/style.css")]
+fn style() -> Css<&'static str> {
+    Css(include_str!("../static/style.css"))
 }
 
 #[get("/env")]
-fn env_info() -> Json<RemoteServerInfo> {
-    Json(collect_env_info())
+fn env_info(config: &State<Arc<ConfigStore>>) -> Json<RemoteServerInfo> {
+    Json(current_env_info(&config.current()))
+}
+
+/// Host information and readiness are collected on every call.
+fn current_env_info(config: &Config) -> RemoteServerInfo {
+    let mut info = collect_env_info();
+    info.readiness = config.readiness.check(&collect_host_state());
+
+    info
+}
+
+/// Protocol versions this server speaks, checked by clients before they
+/// submit tasks.
+#[get("/handshake")]
+fn handshake() -> Json<Handshake> {
+    Json(server_handshake())
+}
+
+fn server_handshake() -> Handshake {
+    Handshake {
+        protocol_version: QBM_PROTOCOL_VERSION,
+        min_protocol_version: QBM_MIN_PROTOCOL_VERSION,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+    }
 }
 
 #[get("/compiler")]
-fn compiler_info() -> Json<Vec<CompilerInfo>> {
-    Json(collect_system_compiler_info())
+fn compiler_info(config: &State<Arc<ConfigStore>>) -> Json<Vec<CompilerInfo>> {
+    Json(configured_compiler_info(&config.current().compilers))
 }
 
 #[post("/run", data = "<task>")]
-fn run(task: Json<BenchmarkTask<'_>>) -> Json<BenchmarkInfo> {
-    let task = task.0.borrow();
-    let file_name = &task.task_uid[..8];
-    let file_ext = Path::new(task.source_file)
-        .extension()
-        .unwrap()
-        .to_str()
-        .unwrap();
-    let no_match_ret = BenchmarkInfo {
-        task_uid: task.task_uid.to_string(),
-        message: "no compiler matches".to_string(),
-        output: "".to_string(),
+async fn run(
+    task: Json<BenchmarkTask>,
+    store: &State<ResultStore>,
+    hub: &State<Arc<EventHub>>,
+    queue: &State<Semaphore>,
+    registry: &State<Arc<NodeRegistry>>,
+    config: &State<Arc<ConfigStore>>,
+) -> TaskResponse {
+    let task = task.into_inner();
+
+    if let Err(message) =
+        validate_task_uid(&task.task_uid).and_then(|_| validate_file_name(&task.source_file))
+    {
+        return task_response(&task.task_uid, Status::BadRequest, message);
+    }
+
+    if let Err(message) = server_handshake().check(task.protocol_version) {
+        return task_response(&task.task_uid, Status::BadRequest, message);
+    }
+
+    if task.iterations > QBM_MAX_ITERATIONS {
+        let message = format!("iterations must not exceed {}", QBM_MAX_ITERATIONS);
+        return task_response(&task.task_uid, Status::BadRequest, message);
+    }
+
+    if hub.is_running(&task.task_uid) {
+        let message = "task is already running".to_string();
+        return task_response(&task.task_uid, Status::Conflict, message);
+    }
+
+    match store.get(&task.task_uid) {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            let message = "task already exists, submit it under a new uid".to_string();
+            return task_response(&task.task_uid, Status::Conflict, message);
+        }
+        Err(e) => {
+            error!("unable to read task {}: {}", task.task_uid, e);
+            let message = "unable to read the result store".to_string();
+            return task_response(&task.task_uid, Status::InternalServerError, message);
+        }
+    }
+
+    let config = config.current();
+
+    if let Err(message) =
+        split_args(&task.compiler_args).and_then(|args| config.compiler_args.check(&args))
+    {
+        return task_response(&task.task_uid, Status::BadRequest, message);
+    }
+
+    let (mut result, environment) = if config.cluster.role == ClusterRole::Coordinator {
+        run_remote(&task, registry, hub).await
+    } else {
+        let result = run_local(&task, &config, hub, queue).await;
+        (result, Some(current_env_info(&config)))
     };
 
-    let compiler = setup_config()
-        .extract::<Config>()
-        .unwrap()
+    if let Some(name) = &task.name {
+        match store.baseline(name) {
+            Ok(Some(baseline)) => result.comparison = config.regression.compare(&baseline, &result),
+            Ok(None) => {}
+            Err(e) => warn!("unable to read baseline {}: {}", name, e),
+        }
+    }
+
+    if let Err(e) = store.insert(&task_record(&task, environment, &result)) {
+        warn!("unable to store task {}: {}", task.task_uid, e);
+    }
+
+    hub.publish(
+        &task.task_uid,
+        TaskEvent::Done {
+            result: Box::new(result.clone()),
+        },
+    );
+
+    (Status::Ok, Json(result))
+}
+
+async fn run_local(
+    task: &BenchmarkTask,
+    config: &Arc<Config>,
+    hub: &Arc<EventHub>,
+    queue: &Semaphore,
+) -> BenchmarkInfo {
+    let compiler = config
         .compilers
-        .into_iter()
-        .find(|p| p.name == task.compiler && p.version == task.compiler_version);
+        .iter()
+        .find(|p| p.name == task.compiler && p.version == task.compiler_version)
+        .map(|p| p.config());
+
+    let compiler = match compiler {
+        Some(compiler) => compiler,
+        None => return BenchmarkInfo::failed(&task.task_uid, "no compiler matches".to_string()),
+    };
+
+    hub.publish(&task.task_uid, TaskEvent::Queued);
+
+    // Benchmarks run one at a time so they do not disturb each other.
+    let _permit = queue.acquire().await;
+
+    if hub.is_cancelled(&task.task_uid) {
+        return BenchmarkInfo::failed(&task.task_uid, TASK_CANCELLED.to_string());
+    }
+
+    let workspace = Path::new(&config.data_dir).join("workspace");
+    let engine = Engine::new(
+        &[compiler],
+        &config.compiler_args,
+        &workspace.join("source").to_string_lossy(),
+        &workspace.join("build").to_string_lossy(),
+    );
+
+    let engine = match engine {
+        Ok(engine) => engine
+            .with_readiness(config.readiness.clone())
+            .with_iteration_timeout(Duration::from_secs(config.iteration_timeout_secs)),
+        Err(message) => return BenchmarkInfo::failed(&task.task_uid, message),
+    };
+
+    let hub = hub.clone();
+    let job = task.clone();
+
+    spawn_blocking(move || execute_task(&hub, &engine, &job))
+        .await
+        .unwrap_or_else(|e| BenchmarkInfo::failed(&task.task_uid, format!("task aborted, {}", e)))
+}
+
+/// Forwards a task to a worker, only `Queued` and `Done` are published for
+/// it on the coordinator.
+async fn run_remote(
+    task: &BenchmarkTask,
+    registry: &Arc<NodeRegistry>,
+    hub: &Arc<EventHub>,
+) -> (BenchmarkInfo, Option<RemoteServerInfo>) {
+    let dispatch = registry.select(&task.compiler, &task.compiler_version, &task.constraints);
+
+    let dispatch = match dispatch {
+        Some(dispatch) => dispatch,
+        None => {
+            let result = BenchmarkInfo::failed(&task.task_uid, unmatched_message(task));
+            return (result, None);
+        }
+    };
+
+    hub.publish(&task.task_uid, TaskEvent::Queued);
+
+    let node = dispatch.node.clone();
+    let body = match serde_json::to_value(task) {
+        Ok(body) => body,
+        Err(e) => {
+            return (
+                BenchmarkInfo::failed(&task.task_uid, e.to_string()),
+                Some(node.environment),
+            )
+        }
+    };
+
+    let mut result = spawn_blocking(move || dispatch.run(&body))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result)
+        .unwrap_or_else(|e| {
+            BenchmarkInfo::failed(
+                &task.task_uid,
+                format!("node {} failed, {}", node.address, e),
+            )
+        });
+    result.node = Some(node.address);
+
+    (result, Some(node.environment))
+}
 
-    if compiler.is_none() {
-        Json(no_match_ret)
+/// Names what no registered node offered, e.g.
+/// `no node has g++ 11.2.0 with cpu_vendor AMD`.
+fn unmatched_message(task: &BenchmarkTask) -> String {
+    let constraints = &task.constraints;
+    let wanted: Vec<String> = [
+        ("cpu_vendor", &constraints.cpu_vendor),
+        ("cpu_brand", &constraints.cpu_brand),
+        ("node", &constraints.node),
+    ]
+    .iter()
+    .filter_map(|(name, value)| value.as_ref().map(|v| format!("{} {}", name, v)))
+    .collect();
+
+    let mut message = format!("no node has {} {}", task.compiler, task.compiler_version);
+    if !wanted.is_empty() {
+        message.push_str(&format!(" with {}", wanted.join(", ")));
+    }
+
+    message
+}
+
+#[get("/tasks/<task_uid>/events")]
+fn task_events(
+    task_uid: &str,
+    hub: &State<Arc<EventHub>>,
+    store: &State<ResultStore>,
+    mut shutdown: Shutdown,
+) -> EventStream![] {
+    let mut subscription = hub.subscribe(task_uid);
+
+    // Results are stored before `Done` is published, so a task that is
+    // neither running nor queued is either finished or not submitted yet.
+    let finished = if subscription.history.is_empty() {
+        store.get(task_uid).ok().flatten()
     } else {
-        let compiler = compiler.unwrap();
-        let compile_result = compile_file(
-            &compiler.path,
-            task.compiler_args,
-            task.source_code,
-            file_name,
-            file_ext,
-        );
-
-        if compile_result.is_err() {
-            Json(BenchmarkInfo {
-                task_uid: task.task_uid.to_string(),
-                message: compile_result.err().unwrap(),
-                output: "".to_string(),
-            })
-        } else {
-            Json(BenchmarkInfo {
-                task_uid: task.task_uid.to_string(),
-                message: compile_result.ok().unwrap(),
-                output: "".to_string(),
-            })
+        None
+    };
+
+    EventStream! {
+        if let Some(record) = finished {
+            yield Event::json(&TaskEvent::Done { result: Box::new(record.result) });
+            return;
+        }
+
+        for event in subscription.history.drain(..).collect::<Vec<_>>() {
+            yield Event::json(&event);
+        }
+
+        loop {
+            let event = select! {
+                event = subscription.receiver.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            let done = matches!(event, TaskEvent::Done { .. });
+
+            yield Event::json(&event);
+
+            if done {
+                break;
+            }
         }
     }
 }
 
+#[get("/tasks")]
+fn active_tasks(hub: &State<Arc<EventHub>>) -> Json<Vec<ActiveTask>> {
+    Json(hub.active())
+}
+
+/// Stops a queued or running task, a coordinator passes it on to its nodes.
+/// Needs the server's secrets, nodes share them with their coordinator.
+#[post("/tasks/<task_uid>/cancel")]
+async fn cancel_task(
+    task_uid: &str,
+    token: BearerToken,
+    hub: &State<Arc<EventHub>>,
+    registry: &State<Arc<NodeRegistry>>,
+    config: &State<Arc<ConfigStore>>,
+) -> Result<Status, (Status, String)> {
+    let secrets = config.current().secrets.clone();
+    token.check(&secrets)?;
+
+    if !hub.cancel(task_uid) {
+        return Err((Status::NotFound, format!("task {} is not active", task_uid)));
+    }
+
+    let nodes = registry.nodes();
+    let task_uid = task_uid.to_string();
+
+    spawn_blocking(move || {
+        for node in nodes {
+            if let Err(e) = send_cancel(&node.address, &secrets, &task_uid) {
+                warn!("unable to cancel {} on {}: {}", task_uid, node.address, e);
+            }
+        }
+    });
+
+    Ok(Status::Accepted)
+}
+
+#[get("/history?<query..>")]
+fn history(
+    query: HistoryQuery,
+    store: &State<ResultStore>,
+) -> Result<Json<Vec<TaskRecord>>, (Status, String)> {
+    query.range().map_err(|e| (Status::BadRequest, e))?;
+
+    store.history(&query).map(Json).map_err(|e| {
+        error!("unable to read history: {}", e);
+        (Status::InternalServerError, e)
+    })
+}
+
+#[get("/r/<task_uid>")]
+fn permalink(task_uid: &str, store: &State<ResultStore>) -> Result<Json<TaskRecord>, Status> {
+    match store.get(task_uid) {
+        Ok(Some(record)) => Ok(Json(record)),
+        Ok(None) => Err(Status::NotFound),
+        Err(e) => {
+            error!("unable to read task {}: {}", task_uid, e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[get("/r/<task_uid>/histogram.svg")]
+fn histogram(task_uid: &str, store: &State<ResultStore>) -> Result<(ContentType, String), Status> {
+    match store.get(task_uid) {
+        Ok(Some(record)) => Ok((
+            ContentType::SVG,
+            histogram_svg(&record.task_uid, &record.result.timings),
+        )),
+        Ok(None) => Err(Status::NotFound),
+        Err(e) => {
+            error!("unable to read task {}: {}", task_uid, e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// `GET /report?uid=<a>&uid=<b>`, the results in the given order.
+#[get("/report?<uid>")]
+fn report(uid: Vec<String>, store: &State<ResultStore>) -> Result<Html<String>, (Status, String)> {
+    if uid.is_empty() {
+        return Err((Status::BadRequest, "no task uid given".to_string()));
+    }
+
+    let mut records = vec![];
+
+    for task_uid in &uid {
+        match store.get(task_uid) {
+            Ok(Some(record)) => records.push(record),
+            Ok(None) => return Err((Status::NotFound, format!("task {} not found", task_uid))),
+            Err(e) => return Err((Status::InternalServerError, e)),
+        }
+    }
+
+    Ok(Html(html_report(&records)))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct BaselineUpdate {
+    task_uid: String,
+}
+
+#[put("/baselines/<name>", data = "<update>")]
+fn set_baseline(
+    name: &str,
+    update: Json<BaselineUpdate>,
+    store: &State<ResultStore>,
+) -> Result<Json<TaskRecord>, (Status, String)> {
+    let record = match store.get(&update.task_uid) {
+        Ok(Some(record)) => record,
+        Ok(None) => return Err((Status::NotFound, "no such task".to_string())),
+        Err(e) => return Err((Status::InternalServerError, e)),
+    };
+
+    // Failed tasks have nothing to compare against.
+    if record.result.timings.is_empty() {
+        let message = format!("task {} has no timings", record.task_uid);
+        return Err((Status::UnprocessableEntity, message));
+    }
+
+    store
+        .set_baseline(name, &record.task_uid)
+        .map_err(|e| (Status::InternalServerError, e))?;
+
+    Ok(Json(record))
+}
+
+#[get("/baselines/<name>")]
+fn get_baseline(name: &str, store: &State<ResultStore>) -> Result<Json<TaskRecord>, Status> {
+    match store.baseline(name) {
+        Ok(Some(record)) => Ok(Json(record)),
+        Ok(None) => Err(Status::NotFound),
+        Err(e) => {
+            error!("unable to read baseline {}: {}", name, e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// Workers authorize with the coordinator's secrets.
+#[post("/nodes/heartbeat", data = "<node>")]
+fn heartbeat(
+    node: Json<NodeInfo>,
+    token: BearerToken,
+    registry: &State<Arc<NodeRegistry>>,
+    config: &State<Arc<ConfigStore>>,
+) -> Result<Status, (Status, String)> {
+    let config = config.current();
+    token.check(&config.secrets)?;
+
+    if config.cluster.role != ClusterRole::Coordinator {
+        return Err((Status::BadRequest, "not a coordinator".to_string()));
+    }
+
+    registry.heartbeat(node.into_inner());
+
+    Ok(Status::NoContent)
+}
+
+#[get("/nodes")]
+fn nodes(registry: &State<Arc<NodeRegistry>>) -> Json<Vec<NodeInfo>> {
+    Json(registry.nodes())
+}
+
+/// Reads the config file again without a restart, only allowed from the
+/// server's own host with its secrets.
+#[post("/admin/reload")]
+async fn reload_config(
+    remote: SocketAddr,
+    token: BearerToken,
+    config: &State<Arc<ConfigStore>>,
+) -> Result<Status, (Status, String)> {
+    if !remote.ip().is_loopback() {
+        return Err((Status::Forbidden, "only allowed from localhost".to_string()));
+    }
+
+    token.check(&config.current().secrets)?;
+
+    let config = Arc::clone(config);
+
+    match spawn_blocking(move || config.reload()).await {
+        Ok(Ok(_)) => Ok(Status::NoContent),
+        Ok(Err(e)) => Err((Status::UnprocessableEntity, e)),
+        Err(e) => Err((Status::InternalServerError, e.to_string())),
+    }
+}
+
+#[get("/openapi.json")]
+fn openapi_document() -> Json<Value> {
+    Json(openapi::document())
+}
+
+/// Everything served under `/api/v1`, also mounted at `/` for older clients.
+fn api_routes() -> Vec<Route> {
+    routes![
+        handshake,
+        env_info,
+        compiler_info,
+        run,
+        task_events,
+        active_tasks,
+        cancel_task,
+        history,
+        permalink,
+        report,
+        histogram,
+        set_baseline,
+        get_baseline,
+        heartbeat,
+        nodes,
+        reload_config,
+        openapi_document
+    ]
+}
+
+/// Sends heartbeats to the coordinator for as long as the server runs.
+fn start_heartbeats(store: Arc<ConfigStore>) {
+    let config = store.current();
+    let (coordinator, advertise) = match (&config.cluster.coordinator, &config.cluster.advertise) {
+        (Some(coordinator), Some(advertise)) => (coordinator.clone(), advertise.clone()),
+        _ => {
+            error!("workers need cluster.coordinator and cluster.advertise");
+            return;
+        }
+    };
+    let interval = Duration::from_secs(config.cluster.heartbeat_secs);
+
+    rocket::tokio::spawn(async move {
+        loop {
+            let coordinator = coordinator.clone();
+            let advertise = advertise.clone();
+            let store = store.clone();
+
+            let beat = spawn_blocking(move || {
+                let config = store.current();
+                let node = NodeInfo {
+                    address: advertise,
+                    environment: current_env_info(&config),
+                    compilers: config
+                        .compilers
+                        .iter()
+                        .map(|c| CompilerInfo {
+                            name: c.name.clone(),
+                            version: c.version.clone(),
+                            target: None,
+                            thread_model: None,
+                        })
+                        .collect(),
+                };
+
+                send_heartbeat(&coordinator, &config.secrets, &node)
+            })
+            .await;
+
+            match beat {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!("heartbeat failed: {}", e),
+                Err(e) => warn!("heartbeat failed: {}", e),
+            }
+
+            sleep(interval).await;
+        }
+    });
+}
+
 #[rocket::main]
 async fn main() {
-    let config = setup_config();
+    let args = server_args();
+    let config_path = args.config_path;
+
+    if args.check_config {
+        match check_config(&config_path) {
+            Ok(_) => println!("{} is valid", config_path),
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{}: {}", config_path, error);
+                }
 
-    rocket::custom(config)
+                process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    let config = match ConfigStore::open(&config_path) {
+        Ok(config) => Arc::new(config),
+        // Rocket only sets up logging once it is built.
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    if let Err(e) = watch_config(config.clone()) {
+        warn!("{}, reload it with POST {}/admin/reload", e, QBM_API_BASE);
+    }
+
+    let data_dir = config.current().data_dir.clone();
+
+    let store = match ResultStore::open(Path::new(&data_dir)) {
+        Ok(store) => store,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let registry = NodeRegistry::new(Duration::from_secs(
+        config.current().cluster.node_timeout_secs,
+    ));
+
+    if let Err(e) = rocket::custom(load_config(&config_path))
+        .manage(config)
+        .manage(store)
+        .manage(registry)
+        .manage(Arc::new(EventHub::default()))
+        .manage(Semaphore::new(1))
         .mount("/", routes![index])
-        .mount("/", routes![env_info])
-        .mount("/", routes![compiler_info])
-        .mount("/", routes![run])
+        .mount("/", routes![script])
+        .mount("/", routes![style])
+        .mount(QBM_API_BASE, api_routes())
+        .mount("/", api_routes())
+        .attach(AdHoc::on_liftoff("Cluster heartbeats", |rocket| {
+            Box::pin(async move {
+                if let Some(config) = rocket.state::<Arc<ConfigStore>>() {
+                    if config.current().cluster.role == ClusterRole::Worker {
+                        start_heartbeats(config.clone())
+                    }
+                }
+            })
+        }))
         .launch()
-        .await;
+        .await
+    {
+        error!("server stopped: {}", e);
+    }
 }
@@ -1 +1,4 @@
 pub const QBM_DEFAULT_CONFIG_FILE_PATH: &str = "qbms.toml";
+pub const QBM_DEFAULT_DATA_DIR: &str = "./data";
+pub const QBM_MAX_ITERATIONS: u32 = 10_000;
+pub const QBM_API_BASE: &str = "/api/v1";
@@ -0,0 +1,334 @@
+use crate::misc::QBM_API_BASE;
+use crate::BaselineUpdate;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+use util::{
+    ActiveTask, BenchmarkInfo, BenchmarkTask, CompilerInfo, Handshake, NodeInfo, RemoteServerInfo,
+    TaskEvent, TaskRecord,
+};
+
+pub const QBM_API_VERSION: &str = "1.0.0";
+
+const COMPATIBILITY_POLICY: &str = "\
+Within v1 changes are additive only: new endpoints, new optional request fields and new \
+response fields. Clients must ignore response fields they do not know. Removing or renaming \
+an endpoint or field, or changing its meaning, needs a new version mounted at /api/v2, and \
+/api/v1 keeps being served next to it for at least one release. The unversioned routes at / \
+are aliases of v1 kept for older clients, new clients should not use them.";
+
+fn schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+    serde_json::to_value(generator.subschema_for::<T>()).unwrap_or_default()
+}
+
+fn json_content(schema: Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+fn response(description: &str) -> Value {
+    json!({ "description": description })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({ "description": description, "content": json_content(schema) })
+}
+
+fn text_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "text/plain": { "schema": { "type": "string" } } }
+    })
+}
+
+fn path_parameter(name: &str) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } })
+}
+
+fn query_parameter(name: &str, schema: Value) -> Value {
+    json!({ "name": name, "in": "query", "required": false, "schema": schema })
+}
+
+/// The OpenAPI 3.0 document of `/api/v1`, schemas come from the wire types.
+pub fn document() -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    let string = json!({ "type": "string" });
+
+    let paths = json!({
+        "/handshake": {
+            "get": {
+                "summary": "Protocol versions the server accepts in BenchmarkTask.protocol_version",
+                "responses": {
+                    "200": json_response("Supported versions", schema::<Handshake>(&mut generator))
+                }
+            }
+        },
+        "/env": {
+            "get": {
+                "summary": "Server hardware, operating system and readiness",
+                "responses": {
+                    "200": json_response("Server environment", schema::<RemoteServerInfo>(&mut generator)),
+                    "500": response("The server config is invalid")
+                }
+            }
+        },
+        "/compiler": {
+            "get": {
+                "summary": "Compilers configured on the server",
+                "responses": {
+                    "200": json_response("Compilers", schema::<Vec<CompilerInfo>>(&mut generator))
+                }
+            }
+        },
+        "/run": {
+            "post": {
+                "summary": "Compile and benchmark a program, blocks until the task is done",
+                "requestBody": {
+                    "required": true,
+                    "content": json_content(schema::<BenchmarkTask>(&mut generator))
+                },
+                "responses": {
+                    "200": json_response("The task is done, without timings it failed and `message` says why", schema::<BenchmarkInfo>(&mut generator)),
+                    "400": json_response("The task is invalid or its protocol version is not supported, see `message`", schema::<BenchmarkInfo>(&mut generator)),
+                    "409": json_response("A task with this uid is already running or stored", schema::<BenchmarkInfo>(&mut generator)),
+                    "500": json_response("The server config is invalid or its result store failed", schema::<BenchmarkInfo>(&mut generator))
+                }
+            }
+        },
+        "/tasks": {
+            "get": {
+                "summary": "Queued and running tasks, oldest first",
+                "responses": {
+                    "200": json_response("Active tasks", schema::<Vec<ActiveTask>>(&mut generator))
+                }
+            }
+        },
+        "/tasks/{task_uid}/events": {
+            "get": {
+                "summary": "Server-sent events with the progress of a task, each `data` is a TaskEvent",
+                "parameters": [path_parameter("task_uid")],
+                "responses": {
+                    "200": {
+                        "description": "Event stream ending with a `done` event",
+                        "content": {
+                            "text/event-stream": { "schema": schema::<TaskEvent>(&mut generator) }
+                        }
+                    }
+                }
+            }
+        },
+        "/tasks/{task_uid}/cancel": {
+            "post": {
+                "summary": "Stop a queued or running task",
+                "parameters": [path_parameter("task_uid")],
+                "security": [{ "secrets": [] }],
+                "responses": {
+                    "202": response("The task will stop"),
+                    "401": text_response("The secrets are missing or wrong"),
+                    "404": text_response("No such active task")
+                }
+            }
+        },
+        "/history": {
+            "get": {
+                "summary": "Finished tasks, newest first",
+                "parameters": [
+                    query_parameter("name", string.clone()),
+                    query_parameter("compiler", string.clone()),
+                    query_parameter("compiler_version", string.clone()),
+                    query_parameter("user", string.clone()),
+                    query_parameter("tag", string.clone()),
+                    query_parameter("fingerprint", string.clone()),
+                    query_parameter("from", json!({ "type": "string", "format": "date-time" })),
+                    query_parameter("to", json!({ "type": "string", "format": "date-time" })),
+                    query_parameter("limit", json!({ "type": "integer", "minimum": 0 }))
+                ],
+                "responses": {
+                    "200": json_response("Matching tasks", schema::<Vec<TaskRecord>>(&mut generator)),
+                    "400": text_response("The query is invalid"),
+                    "500": text_response("The result store failed")
+                }
+            }
+        },
+        "/r/{task_uid}": {
+            "get": {
+                "summary": "A finished task",
+                "parameters": [path_parameter("task_uid")],
+                "responses": {
+                    "200": json_response("The task", schema::<TaskRecord>(&mut generator)),
+                    "404": response("No such task")
+                }
+            }
+        },
+        "/r/{task_uid}/histogram.svg": {
+            "get": {
+                "summary": "Histogram of the per-iteration timings of a task",
+                "parameters": [path_parameter("task_uid")],
+                "responses": {
+                    "200": {
+                        "description": "SVG image",
+                        "content": { "image/svg+xml": { "schema": string.clone() } }
+                    },
+                    "404": response("No such task")
+                }
+            }
+        },
+        "/report": {
+            "get": {
+                "summary": "Self-contained HTML report of one or more tasks, in the given order",
+                "parameters": [{
+                    "name": "uid",
+                    "in": "query",
+                    "required": true,
+                    "style": "form",
+                    "explode": true,
+                    "schema": { "type": "array", "items": string.clone() }
+                }],
+                "responses": {
+                    "200": {
+                        "description": "HTML page",
+                        "content": { "text/html": { "schema": string.clone() } }
+                    },
+                    "400": text_response("No task uid given"),
+                    "404": text_response("A task was not found")
+                }
+            }
+        },
+        "/baselines/{name}": {
+            "get": {
+                "summary": "The baseline of a benchmark",
+                "parameters": [path_parameter("name")],
+                "responses": {
+                    "200": json_response("The baseline task", schema::<TaskRecord>(&mut generator)),
+                    "404": response("No baseline with this name")
+                }
+            },
+            "put": {
+                "summary": "Make a finished task the baseline of a benchmark",
+                "parameters": [path_parameter("name")],
+                "requestBody": {
+                    "required": true,
+                    "content": json_content(schema::<BaselineUpdate>(&mut generator))
+                },
+                "responses": {
+                    "200": json_response("The new baseline task", schema::<TaskRecord>(&mut generator)),
+                    "404": text_response("No such task"),
+                    "422": text_response("The task has no timings, e.g. because it failed")
+                }
+            }
+        },
+        "/nodes/heartbeat": {
+            "post": {
+                "summary": "Register a worker with a coordinator",
+                "security": [{ "secrets": [] }],
+                "requestBody": {
+                    "required": true,
+                    "content": json_content(schema::<NodeInfo>(&mut generator))
+                },
+                "responses": {
+                    "204": response("The node is registered"),
+                    "400": text_response("The server is not a coordinator"),
+                    "401": text_response("The secrets are missing or wrong")
+                }
+            }
+        },
+        "/nodes": {
+            "get": {
+                "summary": "Workers that sent a heartbeat recently",
+                "responses": {
+                    "200": json_response("Live nodes", schema::<Vec<NodeInfo>>(&mut generator))
+                }
+            }
+        },
+        "/admin/reload": {
+            "post": {
+                "summary": "Read the config file again, only allowed from the server's own host",
+                "security": [{ "secrets": [] }],
+                "responses": {
+                    "204": response("The new config is in use"),
+                    "401": text_response("The secrets are missing or wrong"),
+                    "403": text_response("The request did not come from the server's host"),
+                    "422": text_response("The config file is invalid, the running config is kept")
+                }
+            }
+        },
+        "/openapi.json": {
+            "get": {
+                "summary": "This document",
+                "responses": {
+                    "200": json_response("OpenAPI document", json!({ "type": "object" }))
+                }
+            }
+        }
+    });
+
+    let schemas = generator
+        .take_definitions()
+        .into_iter()
+        .map(|(name, schema)| (name, serde_json::to_value(schema).unwrap_or_default()))
+        .collect::<Map<_, _>>();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "QBM",
+            "version": QBM_API_VERSION,
+            "description": COMPATIBILITY_POLICY
+        },
+        "servers": [{ "url": QBM_API_BASE }],
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "secrets": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "The `secrets` of the server's config"
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_every_route() {
+        let document = document();
+
+        for route in crate::api_routes() {
+            // `/r/<task_uid>?<query..>` is `/r/{task_uid}` in OpenAPI.
+            let path = route
+                .uri
+                .path()
+                .replace('<', "{")
+                .replace('>', "}")
+                .replace("..}", "}");
+            let method = route.method.as_str().to_lowercase();
+
+            assert!(
+                document["paths"][&path][&method].is_object(),
+                "{} {} is not documented",
+                method,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_every_reference() {
+        let document = document();
+        let text = document.to_string();
+
+        for reference in text.split("\"$ref\":\"#/components/schemas/").skip(1) {
+            let name = &reference[..reference.find('"').unwrap()];
+
+            assert!(
+                document["components"]["schemas"][name].is_object(),
+                "{} is not defined",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use std::path::Path;
+use time::format_description;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time};
+use util::{BenchmarkInfo, BenchmarkTask, RemoteServerInfo, TaskRecord};
+
+const TASKS_TREE: &str = "tasks";
+const BASELINES_TREE: &str = "baselines";
+/// `created_at` and task uid of every task, so history is read newest first.
+const HISTORY_TREE: &str = "history";
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Filters accepted by `GET /history`, dates are `YYYY-MM-DD` in UTC and both
+/// ends are inclusive.
+#[derive(FromForm, Default)]
+pub struct HistoryQuery {
+    pub name: Option<String>,
+    pub compiler: Option<String>,
+    pub compiler_version: Option<String>,
+    pub user: Option<String>,
+    pub tag: Option<String>,
+    /// `EnvironmentFingerprint::id` of the machine the task ran on.
+    pub fingerprint: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Every finished task, keyed by task uid, in an embedded sled database.
+///
+/// Baselines map a benchmark name to the uid of one of those tasks.
+pub struct ResultStore {
+    tasks: sled::Tree,
+    baselines: sled::Tree,
+    history: sled::Tree,
+}
+
+impl ResultStore {
+    pub fn open(data_dir: &Path) -> Result<ResultStore, String> {
+        let db = sled::open(data_dir)
+            .map_err(|e| format!("unable to open result store {:?}, {}", data_dir, e))?;
+
+        Self::from_db(&db)
+    }
+
+    fn from_db(db: &sled::Db) -> Result<ResultStore, String> {
+        let tasks = db.open_tree(TASKS_TREE).map_err(|e| e.to_string())?;
+        let baselines = db.open_tree(BASELINES_TREE).map_err(|e| e.to_string())?;
+        let history = db.open_tree(HISTORY_TREE).map_err(|e| e.to_string())?;
+
+        let store = ResultStore {
+            tasks,
+            baselines,
+            history,
+        };
+
+        // Stores written before the index existed.
+        if store.history.is_empty() && !store.tasks.is_empty() {
+            store.index_history()?;
+        }
+
+        Ok(store)
+    }
+
+    fn index_history(&self) -> Result<(), String> {
+        for entry in self.tasks.iter() {
+            let (task_uid, value) = entry.map_err(|e| e.to_string())?;
+
+            match serde_json::from_slice::<TaskRecord>(&value) {
+                Ok(record) => {
+                    self.history
+                        .insert(history_key(&record), task_uid)
+                        .map_err(|e| e.to_string())?;
+                }
+                Err(e) => warn!("skip unreadable record: {}", e),
+            }
+        }
+
+        self.history.flush().map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    /// Stores a new record, a stored one is never replaced as its permalink
+    /// may already be shared.
+    pub fn insert(&self, record: &TaskRecord) -> Result<(), String> {
+        let value = serde_json::to_vec(record).map_err(|e| e.to_string())?;
+
+        self.tasks
+            .compare_and_swap(
+                record.task_uid.as_bytes(),
+                None as Option<&[u8]>,
+                Some(value),
+            )
+            .map_err(|e| e.to_string())?
+            .map_err(|_| format!("task {} already exists", record.task_uid))?;
+        self.history
+            .insert(history_key(record), record.task_uid.as_bytes())
+            .map_err(|e| e.to_string())?;
+        self.tasks.flush().map_err(|e| e.to_string())?;
+        self.history.flush().map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, task_uid: &str) -> Result<Option<TaskRecord>, String> {
+        match self
+            .tasks
+            .get(task_uid.as_bytes())
+            .map_err(|e| e.to_string())?
+        {
+            Some(value) => serde_json::from_slice(&value)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_baseline(&self, name: &str, task_uid: &str) -> Result<(), String> {
+        self.baselines
+            .insert(name.as_bytes(), task_uid.as_bytes())
+            .map_err(|e| e.to_string())?;
+        self.baselines.flush().map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    pub fn baseline(&self, name: &str) -> Result<Option<TaskRecord>, String> {
+        let task_uid = match self
+            .baselines
+            .get(name.as_bytes())
+            .map_err(|e| e.to_string())?
+        {
+            Some(task_uid) => String::from_utf8_lossy(&task_uid).into_owned(),
+            None => return Ok(None),
+        };
+
+        self.get(&task_uid)
+    }
+
+    /// Records matching `query`, newest first. Only stops early once `limit`
+    /// records matched or the records are older than `from`.
+    pub fn history(&self, query: &HistoryQuery) -> Result<Vec<TaskRecord>, String> {
+        let (from, to) = query.range()?;
+        let limit = query.limit.unwrap_or(usize::MAX);
+
+        let mut records = vec![];
+
+        for entry in self.history.iter().rev() {
+            if records.len() >= limit {
+                break;
+            }
+
+            let (_, task_uid) = entry.map_err(|e| e.to_string())?;
+            let value = match self.tasks.get(&task_uid).map_err(|e| e.to_string())? {
+                Some(value) => value,
+                None => continue,
+            };
+            let record: TaskRecord = match serde_json::from_slice(&value) {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!("skip unreadable record: {}", e);
+                    continue;
+                }
+            };
+
+            if from.is_some_and(|f| record.created_at < f) {
+                break;
+            }
+
+            if query.matches(&record) && to.is_none_or(|t| record.created_at < t) {
+                records.push(record);
+            }
+        }
+
+        Ok(records)
+    }
+}
+
+impl HistoryQuery {
+    /// `from` and `to` as timestamps, `to` is exclusive.
+    pub fn range(&self) -> Result<(Option<i64>, Option<i64>), String> {
+        let from = self.from.as_deref().map(parse_date).transpose()?;
+        let to = self
+            .to
+            .as_deref()
+            .map(parse_date)
+            .transpose()?
+            .map(|t| t + SECONDS_PER_DAY);
+
+        Ok((from, to))
+    }
+
+    fn matches(&self, record: &TaskRecord) -> bool {
+        self.name
+            .as_ref()
+            .is_none_or(|n| record.name.as_ref() == Some(n))
+            && self.compiler.as_ref().is_none_or(|c| &record.compiler == c)
+            && self
+                .compiler_version
+                .as_ref()
+                .is_none_or(|v| &record.compiler_version == v)
+            && self
+                .user
+                .as_ref()
+                .is_none_or(|u| record.user.as_ref() == Some(u))
+            && self.tag.as_ref().is_none_or(|t| record.tags.contains(t))
+            && self.fingerprint.as_ref().is_none_or(|f| {
+                record.result.fingerprint.as_ref().map(|r| r.id()) == Some(f.to_string())
+            })
+    }
+}
+
+/// Sorts by creation time, the uid keeps tasks of the same second apart.
+fn history_key(record: &TaskRecord) -> Vec<u8> {
+    let mut key = (record.created_at.max(0) as u64).to_be_bytes().to_vec();
+    key.extend_from_slice(record.task_uid.as_bytes());
+    key
+}
+
+pub fn task_record(
+    task: &BenchmarkTask,
+    environment: Option<RemoteServerInfo>,
+    result: &BenchmarkInfo,
+) -> TaskRecord {
+    TaskRecord {
+        task_uid: task.task_uid.clone(),
+        compiler: task.compiler.clone(),
+        compiler_version: task.compiler_version.clone(),
+        source_file: task.source_file.clone(),
+        source_code: task.source_code.clone(),
+        compiler_args: task.compiler_args.clone(),
+        assembly: task.assembly,
+        iterations: task.iterations,
+        name: task.name.clone(),
+        user: task.user.clone(),
+        tags: task.tags.clone(),
+        created_at: OffsetDateTime::now_utc().unix_timestamp(),
+        environment,
+        result: result.clone(),
+    }
+}
+
+fn parse_date(date: &str) -> Result<i64, String> {
+    let format = format_description::parse("[year]-[month]-[day]").map_err(|e| e.to_string())?;
+    let date = Date::parse(date, &format).map_err(|e| format!("invalid date {:?}, {}", date, e))?;
+
+    Ok(PrimitiveDateTime::new(date, Time::MIDNIGHT)
+        .assume_utc()
+        .unix_timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary_store() -> ResultStore {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+
+        ResultStore::from_db(&db).unwrap()
+    }
+
+    fn record(task_uid: &str, compiler: &str, user: &str, tags: &[&str], date: &str) -> TaskRecord {
+        TaskRecord {
+            task_uid: task_uid.to_string(),
+            compiler: compiler.to_string(),
+            compiler_version: "1.0.0".to_string(),
+            source_file: "main.cpp".to_string(),
+            source_code: "".to_string(),
+            compiler_args: "".to_string(),
+            assembly: false,
+            iterations: 1,
+            name: None,
+            user: Some(user.to_string()),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            created_at: parse_date(date).unwrap() + 3600,
+            environment: None,
+            result: BenchmarkInfo::new(task_uid),
+        }
+    }
+
+    fn uids(records: Vec<TaskRecord>) -> Vec<String> {
+        records.into_iter().map(|r| r.task_uid).collect()
+    }
+
+    #[test]
+    fn never_replaces_records() {
+        let store = temporary_store();
+        let first = record("task-a", "g++", "alice", &[], "2021-10-01");
+
+        store.insert(&first).unwrap();
+        assert!(store
+            .insert(&record("task-a", "clang++", "bob", &[], "2021-10-02"))
+            .unwrap_err()
+            .contains("already exists"));
+        assert_eq!(store.get("task-a").unwrap().unwrap().compiler, "g++");
+    }
+
+    #[test]
+    fn filters_history() {
+        let store = temporary_store();
+
+        store
+            .insert(&record(
+                "task-a",
+                "g++",
+                "alice",
+                &["nightly"],
+                "2021-10-01",
+            ))
+            .unwrap();
+        store
+            .insert(&record("task-b", "clang++", "bob", &[], "2021-10-05"))
+            .unwrap();
+        store
+            .insert(&record("task-c", "g++", "bob", &["nightly"], "2021-10-09"))
+            .unwrap();
+
+        let all = store.history(&HistoryQuery::default()).unwrap();
+        assert_eq!(uids(all), ["task-c", "task-b", "task-a"]);
+
+        let query = HistoryQuery {
+            compiler: Some("g++".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(uids(store.history(&query).unwrap()), ["task-c", "task-a"]);
+
+        let query = HistoryQuery {
+            user: Some("bob".to_string()),
+            tag: Some("nightly".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(uids(store.history(&query).unwrap()), ["task-c"]);
+
+        let query = HistoryQuery {
+            from: Some("2021-10-05".to_string()),
+            to: Some("2021-10-05".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(uids(store.history(&query).unwrap()), ["task-b"]);
+
+        let query = HistoryQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(uids(store.history(&query).unwrap()), ["task-c"]);
+    }
+
+    #[test]
+    fn indexes_stores_written_without_history() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tasks = db.open_tree(TASKS_TREE).unwrap();
+
+        for task in [
+            record("task-a", "g++", "alice", &[], "2021-10-01"),
+            record("task-b", "g++", "alice", &[], "2021-10-05"),
+        ] {
+            tasks
+                .insert(task.task_uid.as_bytes(), serde_json::to_vec(&task).unwrap())
+                .unwrap();
+        }
+
+        let store = ResultStore::from_db(&db).unwrap();
+        let all = store.history(&HistoryQuery::default()).unwrap();
+
+        assert_eq!(uids(all), ["task-b", "task-a"]);
+    }
+
+    #[test]
+    fn rejects_bad_dates() {
+        let store = temporary_store();
+        let query = HistoryQuery {
+            from: Some("last week".to_string()),
+            ..Default::default()
+        };
+
+        assert!(store.history(&query).is_err());
+    }
+
+    #[test]
+    fn gets_record_by_uid() {
+        let store = temporary_store();
+
+        store
+            .insert(&record("task-a", "g++", "alice", &[], "2021-10-01"))
+            .unwrap();
+
+        assert_eq!(store.get("task-a").unwrap().unwrap().compiler, "g++");
+        assert!(store.get("task-b").unwrap().is_none());
+    }
+
+    #[test]
+    fn resolves_baselines() {
+        let store = temporary_store();
+
+        store
+            .insert(&record("task-a", "g++", "alice", &[], "2021-10-01"))
+            .unwrap();
+        store.set_baseline("fib", "task-a").unwrap();
+
+        assert_eq!(store.baseline("fib").unwrap().unwrap().task_uid, "task-a");
+        assert!(store.baseline("sort").unwrap().is_none());
+    }
+}
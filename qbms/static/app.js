@@ -0,0 +1,268 @@
+"use strict";
+
+const $ = (id) => document.getElementById(id);
+const API = "/api/v1";
+const DRAFT_KEY = "qbm-draft";
+const SAMPLE = `#include <cstdio>
+
+int main() {
+    long sum = 0;
+    for (long i = 0; i < 50000000; i++) {
+        sum += i;
+    }
+    std::printf("%ld\\n", sum);
+}
+`;
+
+let compilers = [];
+let selected = new Set();
+
+function formatNs(ns) {
+  if (ns >= 1e9) return (ns / 1e9).toFixed(3) + " s";
+  if (ns >= 1e6) return (ns / 1e6).toFixed(3) + " ms";
+  if (ns >= 1e3) return (ns / 1e3).toFixed(3) + " us";
+  return ns + " ns";
+}
+
+function mean(timings) {
+  return timings.length ? timings.reduce((a, b) => a + b, 0) / timings.length : null;
+}
+
+// crypto.randomUUID is only available to secure contexts.
+function taskUid() {
+  const bytes = crypto.getRandomValues(new Uint8Array(16));
+  bytes[6] = (bytes[6] & 0x0f) | 0x40;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+  const hex = Array.from(bytes, (b) => b.toString(16).padStart(2, "0")).join("");
+  return `${hex.slice(0, 8)}-${hex.slice(8, 12)}-${hex.slice(12, 16)}-${hex.slice(16, 20)}-${hex.slice(20)}`;
+}
+
+function element(tag, text, className) {
+  const node = document.createElement(tag);
+  if (text !== undefined) node.textContent = text;
+  if (className) node.className = className;
+  return node;
+}
+
+async function getJson(url) {
+  const response = await fetch(url);
+  if (!response.ok) throw new Error(`${url}: ${response.status}`);
+  return response.json();
+}
+
+async function loadEnv() {
+  try {
+    const env = await getJson(`${API}/env`);
+    $("env").textContent = `${env.cpu_brand.trim()}, ${env.cpu_cores} cores, ${env.platform} ${env.kernel_version}`;
+  } catch (e) {
+    $("env").textContent = e.message;
+  }
+}
+
+async function loadCompilers() {
+  compilers = await getJson(`${API}/compiler`);
+  const select = $("compiler");
+  select.replaceChildren(
+    ...compilers.map((c, i) => {
+      const option = element("option", `${c.name} ${c.version}`);
+      option.value = i;
+      return option;
+    })
+  );
+}
+
+function showResult(result) {
+  const body = $("result-body");
+  body.replaceChildren();
+
+  if (result.timings.length) {
+    const sorted = [...result.timings].sort((a, b) => a - b);
+    body.append(
+      element(
+        "p",
+        `${result.timings.length} iterations, mean ${formatNs(mean(result.timings))}, ` +
+          `min ${formatNs(sorted[0])}, max ${formatNs(sorted[sorted.length - 1])}`
+      )
+    );
+  }
+
+  if (result.comparison) {
+    const c = result.comparison;
+    const p = c.p_value === null || c.p_value === undefined ? "" : `, p=${c.p_value.toFixed(4)}`;
+    body.append(
+      element(
+        "p",
+        `${(c.change * 100).toFixed(1)}% vs baseline ${c.baseline_uid.slice(0, 8)}${p}` +
+          (c.regression ? " REGRESSION" : ""),
+        c.regression ? "regression" : ""
+      )
+    );
+  }
+
+  for (const warning of result.warnings || []) {
+    body.append(element("p", `warning: ${warning}`, "warning"));
+  }
+
+  if (result.message.trim()) body.append(element("pre", result.message));
+  if (result.output) body.append(element("pre", result.output));
+
+  if (result.timings.length) {
+    const chart = element("img", undefined, "chart");
+    chart.src = `${API}/r/${result.task_uid}/histogram.svg`;
+    chart.alt = "Histogram of the per-iteration timings";
+    body.append(chart);
+
+    const report = element("a", "Full report");
+    report.href = `${API}/report?uid=${result.task_uid}`;
+    report.target = "_blank";
+    body.append(element("p"));
+    body.lastChild.append(report);
+  }
+}
+
+function follow(uid, iterations) {
+  const events = new EventSource(`${API}/tasks/${uid}/events`);
+
+  events.onmessage = (message) => {
+    const event = JSON.parse(message.data);
+
+    switch (event.state) {
+      case "iteration":
+        $("status").textContent = `running ${event.iteration}/${event.iterations}`;
+        break;
+      case "done":
+        events.close();
+        break;
+      case "output":
+        break;
+      default:
+        $("status").textContent = event.state;
+    }
+  };
+  events.onerror = () => events.close();
+
+  $("status").textContent = `submitting ${iterations} iterations`;
+  return events;
+}
+
+async function run() {
+  const compiler = compilers[$("compiler").value];
+  if (!compiler) {
+    $("status").textContent = "no compiler available";
+    return;
+  }
+
+  const uid = taskUid();
+  const task = {
+    protocol_version: 1,
+    task_uid: uid,
+    compiler: compiler.name,
+    compiler_version: compiler.version,
+    source_file: $("source-file").value,
+    source_code: $("source").value,
+    compiler_args: $("args").value,
+    assembly: false,
+    iterations: Number($("iterations").value) || 1,
+    name: $("name").value.trim() || null,
+    tags: [],
+  };
+
+  $("run").disabled = true;
+  const events = follow(uid, task.iterations);
+
+  try {
+    const response = await fetch(`${API}/run`, {
+      method: "POST",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify(task),
+    });
+    const result = await response.json();
+
+    $("status").textContent = response.ok ? uid.slice(0, 8) : `rejected (${response.status})`;
+    showResult(result);
+  } catch (e) {
+    $("status").textContent = e.message;
+  } finally {
+    events.close();
+    $("run").disabled = false;
+    loadHistory();
+  }
+}
+
+async function loadHistory() {
+  const records = await getJson(`${API}/history?limit=30`);
+
+  $("history-rows").replaceChildren(
+    ...records.map((record) => {
+      const row = element("tr");
+      const check = element("input");
+      check.type = "checkbox";
+      check.checked = selected.has(record.task_uid);
+      check.onclick = (e) => {
+        e.stopPropagation();
+        check.checked ? selected.add(record.task_uid) : selected.delete(record.task_uid);
+        $("compare").disabled = selected.size < 2;
+      };
+
+      const cell = element("td");
+      cell.append(check);
+      const average = mean(record.result.timings);
+      row.append(
+        cell,
+        element("td", new Date(record.created_at * 1000).toLocaleString()),
+        element("td", record.name || ""),
+        element("td", `${record.compiler} ${record.compiler_version}`),
+        element("td", record.compiler_args),
+        element("td", average === null ? "failed" : formatNs(average), "num")
+      );
+      row.onclick = () => load(record);
+
+      return row;
+    })
+  );
+}
+
+// Puts a past task back into the editor.
+function load(record) {
+  $("source").value = record.source_code;
+  $("source-file").value = record.source_file;
+  $("args").value = record.compiler_args;
+  $("iterations").value = record.iterations;
+  $("name").value = record.name || "";
+
+  const index = compilers.findIndex(
+    (c) => c.name === record.compiler && c.version === record.compiler_version
+  );
+  if (index >= 0) $("compiler").value = index;
+
+  $("status").textContent = record.task_uid.slice(0, 8);
+  showResult(record.result);
+}
+
+function setupEditor() {
+  const source = $("source");
+  source.value = localStorage.getItem(DRAFT_KEY) || SAMPLE;
+  source.oninput = () => localStorage.setItem(DRAFT_KEY, source.value);
+
+  source.onkeydown = (e) => {
+    if (e.key === "Tab") {
+      e.preventDefault();
+      source.setRangeText("    ", source.selectionStart, source.selectionEnd, "end");
+      localStorage.setItem(DRAFT_KEY, source.value);
+    } else if (e.key === "Enter" && (e.ctrlKey || e.metaKey)) {
+      e.preventDefault();
+      run();
+    }
+  };
+}
+
+$("run").onclick = run;
+$("compare").onclick = () => {
+  const query = [...selected].map((uid) => `uid=${encodeURIComponent(uid)}`).join("&");
+  window.open(`${API}/report?${query}`, "_blank");
+};
+
+setupEditor();
+loadEnv();
+loadCompilers().catch((e) => ($("status").textContent = e.message));
+loadHistory().catch((e) => ($("status").textContent = e.message));
@@ -0,0 +1,188 @@
+//! Starts `qbms` processes, e.g. a coordinator and its workers.
+
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A running `qbms`, killed when dropped.
+struct Server {
+    child: Child,
+    address: String,
+}
+
+impl Server {
+    fn start(root: &Path, name: &str, secrets: &str, cluster: &str) -> Server {
+        let port = free_port();
+        let address = format!("127.0.0.1:{}", port);
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+
+        let config = dir.join("qbms.toml");
+        fs::write(
+            &config,
+            format!(
+                "port = {}\nsecrets = \"{}\"\ndata_dir = \"{}\"\ncompilers = []\n\n[cluster]\n{}",
+                port,
+                secrets,
+                dir.join("data").display(),
+                cluster.replace("{address}", &address)
+            ),
+        )
+        .unwrap();
+
+        let child = Command::new(env!("CARGO_BIN_EXE_qbms"))
+            .arg("-f")
+            .arg(&config)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let server = Server { child, address };
+        server.wait_until_up();
+        server
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://{}/api/v1{}", self.address, path)
+    }
+
+    fn wait_until_up(&self) {
+        let deadline = Instant::now() + Duration::from_secs(20);
+
+        while ureq::get(&self.url("/handshake")).call().is_err() {
+            assert!(Instant::now() < deadline, "{} did not start", self.address);
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn nodes(&self) -> Vec<serde_json::Value> {
+        ureq::get(&self.url("/nodes"))
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap()
+    }
+
+    fn node_addresses(&self) -> Vec<String> {
+        self.nodes()
+            .iter()
+            .map(|n| n["address"].as_str().unwrap().to_string())
+            .collect()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[test]
+fn coordinator_only_registers_workers_with_its_secrets() {
+    let root = env::temp_dir().join(format!("qbms-cluster-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+
+    let coordinator = Server::start(&root, "coordinator", "s3cret", "role = \"coordinator\"\n");
+    let worker_cluster = format!(
+        "role = \"worker\"\ncoordinator = \"{}\"\nadvertise = \"{{address}}\"\nheartbeat_secs = 1\n",
+        coordinator.address
+    );
+    let worker = Server::start(&root, "worker", "s3cret", &worker_cluster);
+    let intruder = Server::start(&root, "intruder", "guess", &worker_cluster);
+
+    let deadline = Instant::now() + Duration::from_secs(20);
+    while !coordinator.node_addresses().contains(&worker.address) {
+        assert!(Instant::now() < deadline, "the worker never registered");
+        thread::sleep(Duration::from_millis(200));
+    }
+
+    // The intruder has sent heartbeats by now as well.
+    thread::sleep(Duration::from_millis(1500));
+    assert_eq!(coordinator.node_addresses(), [worker.address.as_str()]);
+
+    let mut node = coordinator.nodes().remove(0);
+    node["address"] = intruder.address.clone().into();
+    let anonymous = ureq::post(&coordinator.url("/nodes/heartbeat")).send_json(node);
+    assert!(matches!(anonymous, Err(ureq::Error::Status(401, _))));
+    assert!(!coordinator.node_addresses().contains(&intruder.address));
+
+    drop((worker, intruder, coordinator));
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn reload_and_cancel_need_the_secrets() {
+    let root = env::temp_dir().join(format!("qbms-secrets-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+
+    let server = Server::start(&root, "server", "s3cret", "role = \"standalone\"\n");
+    let post = |path: &str, token: &str| {
+        let response = ureq::post(&server.url(path))
+            .set("Authorization", &format!("Bearer {}", token))
+            .call();
+
+        match response {
+            Ok(response) => response.status(),
+            Err(ureq::Error::Status(status, _)) => status,
+            Err(e) => panic!("{}", e),
+        }
+    };
+
+    assert_eq!(post("/admin/reload", "guess"), 401);
+    assert_eq!(post("/admin/reload", "s3cret"), 204);
+    assert_eq!(post("/tasks/0123456789abcdef/cancel", "guess"), 401);
+    assert_eq!(post("/tasks/0123456789abcdef/cancel", "s3cret"), 404);
+
+    drop(server);
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn coordinator_names_constraints_no_node_matches() {
+    let root = env::temp_dir().join(format!("qbms-unmatched-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+
+    let coordinator = Server::start(&root, "coordinator", "s3cret", "role = \"coordinator\"\n");
+    let task = serde_json::json!({
+        "task_uid": "fedcba9876543210",
+        "compiler": "g++",
+        "compiler_version": "11.2.0",
+        "source_file": "main.cpp",
+        "source_code": "int main() {}",
+        "constraints": { "cpu_vendor": "AMD" }
+    });
+
+    let result: serde_json::Value = ureq::post(&coordinator.url("/run"))
+        .send_json(task)
+        .unwrap()
+        .into_json()
+        .unwrap();
+    assert_eq!(
+        result["message"],
+        "no node has g++ 11.2.0 with cpu_vendor AMD"
+    );
+
+    let record: serde_json::Value = ureq::get(&coordinator.url("/r/fedcba9876543210"))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+    assert!(record["environment"].is_null());
+
+    drop(coordinator);
+    let _ = fs::remove_dir_all(&root);
+}
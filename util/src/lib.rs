@@ -1,14 +1,49 @@
+mod uargs;
+mod uchart;
+mod ufs;
 mod ulog;
+mod ureport;
+mod ustat;
 mod uto;
 
+pub use crate::uargs::split_args;
+pub use crate::uargs::ArgPolicy;
+pub use crate::uchart::box_plot_svg;
+pub use crate::uchart::box_plots;
+pub use crate::uchart::box_stats;
+pub use crate::uchart::format_ns;
+pub use crate::uchart::histogram;
+pub use crate::uchart::histogram_svg;
+pub use crate::uchart::BoxStats;
+pub use crate::ufs::check_executable;
+pub use crate::ufs::create_task_workspace;
+pub use crate::ufs::validate_file_name;
+pub use crate::ufs::validate_task_uid;
+pub use crate::ufs::workspace_file;
+pub use crate::ufs::write_workspace_file;
+pub use crate::ufs::TaskWorkspace;
 pub use crate::ulog::__init_logger as init_logger;
+pub use crate::ureport::html_report;
+pub use crate::ustat::mean;
+pub use crate::ustat::variance;
+pub use crate::ustat::welch_t_test;
+pub use crate::uto::ActiveTask;
+pub use crate::uto::BaselineComparison;
 pub use crate::uto::BenchmarkInfo;
 pub use crate::uto::BenchmarkTask;
 pub use crate::uto::CompilerInfo;
+pub use crate::uto::EnvironmentFingerprint;
+pub use crate::uto::Handshake;
+pub use crate::uto::NodeConstraints;
+pub use crate::uto::NodeInfo;
 pub use crate::uto::ProcessorInfo;
 pub use crate::uto::RemoteServerInfo;
+pub use crate::uto::TaskEvent;
+pub use crate::uto::TaskRecord;
+pub use crate::uto::TaskState;
+pub use crate::uto::QBM_MIN_PROTOCOL_VERSION;
+pub use crate::uto::QBM_PROTOCOL_VERSION;
 
-#[macro_use]
 extern crate log;
 #[doc(hidden)]
 pub use log::*;
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+
+/// Flags that can load code or write files outside the task workspace.
+const DEFAULT_DENIED_ARGS: &[&str] = &[
+    "-B",
+    "-fplugin",
+    "-wrapper",
+    "-specs",
+    "-o",
+    "@",
+    "-Xclang",
+    "-Xlinker",
+    "-Wl,",
+    "-fuse-ld",
+    "-save-temps",
+    "-fprofile-generate",
+    "-fdump-",
+    "-include",
+    "-imacros",
+    "--sysroot",
+    "-isysroot",
+];
+
+/// Flags taking a directory, denied when it is outside the task workspace.
+const DEFAULT_DENIED_PATH_ARGS: &[&str] = &["-iquote", "-isystem", "-idirafter"];
+
+/// Allow/deny list applied to client supplied compiler arguments.
+///
+/// An entry matches an argument when the argument starts with it, so `-B`
+/// rejects `-B/tmp` as well. A `--flag` spelling is matched like `-flag`.
+/// When `allow` is non-empty every argument must match one of its entries.
+///
+/// `deny_paths` entries only reject the flag when its value, joined or in the
+/// next argument, is an absolute path or climbs out with `..`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ArgPolicy {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+    pub deny_paths: Vec<String>,
+}
+
+impl Default for ArgPolicy {
+    fn default() -> Self {
+        ArgPolicy {
+            allow: vec![],
+            deny: DEFAULT_DENIED_ARGS.iter().map(|a| a.to_string()).collect(),
+            deny_paths: DEFAULT_DENIED_PATH_ARGS
+                .iter()
+                .map(|a| a.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ArgPolicy {
+    pub fn check<S: AsRef<str>>(&self, args: &[S]) -> Result<(), String> {
+        for (i, arg) in args.iter().enumerate() {
+            let arg = arg.as_ref();
+
+            if self.deny.iter().any(|e| Self::matches(e, arg)) {
+                return Err(format!("compiler argument not allowed: {:?}", arg));
+            }
+
+            for entry in self.deny_paths.iter().filter(|e| Self::matches(e, arg)) {
+                let joined = arg.trim_start_matches('-');
+                let value = match &joined[entry.trim_start_matches('-').len()..] {
+                    "" => args.get(i + 1).map(|a| a.as_ref()).unwrap_or(""),
+                    value => value.trim_start_matches('='),
+                };
+
+                if Self::is_outside_workspace(value) {
+                    return Err(format!(
+                        "compiler argument not allowed: {:?} {:?}",
+                        entry, value
+                    ));
+                }
+            }
+
+            if !self.allow.is_empty() && !self.allow.iter().any(|e| Self::matches(e, arg)) {
+                return Err(format!("compiler argument not allowed: {:?}", arg));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn matches(entry: &str, arg: &str) -> bool {
+        arg.starts_with(entry) || (arg.starts_with("--") && arg[1..].starts_with(entry))
+    }
+
+    fn is_outside_workspace(path: &str) -> bool {
+        let path = Path::new(path);
+
+        path.has_root()
+            || path
+                .components()
+                .any(|c| matches!(c, Component::ParentDir | Component::Prefix(_)))
+    }
+}
+
+/// Splits a client supplied argument string into argv using POSIX shell
+/// quoting rules. Nothing is expanded, the result is never passed to a shell.
+pub fn split_args(args: &str) -> Result<Vec<String>, String> {
+    shell_words::split(args).map_err(|e| format!("invalid compiler arguments, {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_quoted_args() {
+        assert_eq!(
+            split_args("-O2  -DNAME='a b' \"-I x\"").unwrap(),
+            vec!["-O2", "-DNAME=a b", "-I x"]
+        );
+        assert_eq!(split_args("-O2; rm -rf ~").unwrap().len(), 4);
+        assert!(split_args("-DNAME='a").is_err());
+    }
+
+    #[test]
+    fn default_policy_denies_dangerous_flags() {
+        let policy = ArgPolicy::default();
+
+        assert!(policy
+            .check(&["-O2", "-std=c++17", "-march=native"])
+            .is_ok());
+
+        for arg in [
+            "-fplugin=/tmp/evil.so",
+            "-B/tmp",
+            "--specs=evil",
+            "-o/tmp/out",
+            "@/etc/passwd",
+            "-Wl,-plugin,/tmp/evil.so",
+            "-include/etc/passwd",
+            "-include",
+            "-imacros/tmp/evil.h",
+            "--sysroot=/tmp/evil",
+            "-isysroot/tmp/evil",
+        ] {
+            assert!(policy.check(&[arg]).is_err(), "{:?} accepted", arg);
+        }
+    }
+
+    #[test]
+    fn default_policy_denies_include_dirs_outside_workspace() {
+        let policy = ArgPolicy::default();
+
+        assert!(policy.check(&["-isystem", "include", "-iquote."]).is_ok());
+
+        for args in [
+            vec!["-isystem/usr/local/include"],
+            vec!["-isystem", "/usr/local/include"],
+            vec!["-iquote", "../../secrets"],
+            vec!["--isystem=/tmp"],
+            vec!["-idirafter/tmp"],
+        ] {
+            assert!(policy.check(&args).is_err(), "{:?} accepted", args);
+        }
+    }
+
+    #[test]
+    fn allow_list_restricts_args() {
+        let policy = ArgPolicy {
+            allow: vec!["-O".to_string(), "-std=".to_string()],
+            deny: vec![],
+            deny_paths: vec![],
+        };
+
+        assert!(policy.check(&["-O3", "-std=c++20"]).is_ok());
+        assert!(policy.check(&["-O3", "-g"]).is_err());
+    }
+}
@@ -0,0 +1,340 @@
+use std::fmt::Write;
+
+const BAR_WIDTH: usize = 40;
+const PLOT_WIDTH: usize = 60;
+const SVG_WIDTH: f64 = 640.0;
+const SVG_MARGIN: f64 = 40.0;
+
+pub fn format_ns(ns: u64) -> String {
+    if ns >= 1_000_000_000 {
+        format!("{:.3} s", ns as f64 / 1e9)
+    } else if ns >= 1_000_000 {
+        format!("{:.3} ms", ns as f64 / 1e6)
+    } else if ns >= 1_000 {
+        format!("{:.3} us", ns as f64 / 1e3)
+    } else {
+        format!("{} ns", ns)
+    }
+}
+
+/// Minimum, quartiles and maximum of a sample.
+#[derive(Debug, PartialEq)]
+pub struct BoxStats {
+    pub min: u64,
+    pub q1: u64,
+    pub median: u64,
+    pub q3: u64,
+    pub max: u64,
+}
+
+pub fn box_stats(timings: &[u64]) -> Option<BoxStats> {
+    if timings.is_empty() {
+        return None;
+    }
+
+    let mut sorted = timings.to_vec();
+    sorted.sort_unstable();
+
+    let quantile = |q: f64| {
+        let rank = q * (sorted.len() - 1) as f64;
+        let (low, high) = (sorted[rank.floor() as usize], sorted[rank.ceil() as usize]);
+
+        low + ((high - low) as f64 * rank.fract()).round() as u64
+    };
+
+    Some(BoxStats {
+        min: sorted[0],
+        q1: quantile(0.25),
+        median: quantile(0.5),
+        q3: quantile(0.75),
+        max: sorted[sorted.len() - 1],
+    })
+}
+
+/// Counts of `timings` in equally wide bins between their minimum and
+/// maximum, `(lower bound, bin width, counts)`.
+fn bins(timings: &[u64]) -> (u64, u64, Vec<usize>) {
+    let min = timings.iter().min().copied().unwrap_or_default();
+    let max = timings.iter().max().copied().unwrap_or_default();
+
+    // Sturges' rule, enough to tell one peak from two.
+    let count = ((timings.len() as f64).log2().ceil() as usize + 1).clamp(1, 20);
+    let count = if max == min { 1 } else { count };
+    let width = ((max - min) / count as u64 + 1).max(1);
+
+    let mut counts = vec![0; count];
+
+    for &t in timings {
+        counts[(((t - min) / width) as usize).min(count - 1)] += 1;
+    }
+
+    (min, width, counts)
+}
+
+/// A horizontal bar per bin of the per-iteration timings.
+pub fn histogram(timings: &[u64]) -> Vec<String> {
+    if timings.is_empty() {
+        return vec![];
+    }
+
+    let (min, width, counts) = bins(timings);
+    let most = counts.iter().max().copied().unwrap_or(1).max(1);
+
+    counts
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| {
+            let low = min + i as u64 * width;
+            let bar = "█".repeat((count * BAR_WIDTH).div_ceil(most));
+
+            format!(
+                "{:>12} - {:<12} │{:<width$} {}",
+                format_ns(low),
+                format_ns(low + width),
+                bar,
+                count,
+                width = BAR_WIDTH
+            )
+        })
+        .collect()
+}
+
+/// One box plot per labelled sample, drawn on a shared scale.
+pub fn box_plots(series: &[(&str, &[u64])]) -> Vec<String> {
+    let stats = series
+        .iter()
+        .filter_map(|(label, timings)| box_stats(timings).map(|s| (*label, s)))
+        .collect::<Vec<_>>();
+
+    let (low, high) = match scale(&stats) {
+        Some(scale) => scale,
+        None => return vec![],
+    };
+    let label_width = stats
+        .iter()
+        .map(|(l, _)| l.chars().count())
+        .max()
+        .unwrap_or(0);
+    let position = |v: u64| {
+        ((v - low) as f64 / (high - low).max(1) as f64 * (PLOT_WIDTH - 1) as f64).round() as usize
+    };
+
+    let mut lines = stats
+        .iter()
+        .map(|(label, s)| {
+            let mut cells = vec![' '; PLOT_WIDTH];
+
+            cells[position(s.min)..=position(s.max)].fill('─');
+            cells[position(s.q1)..=position(s.q3)].fill('█');
+            cells[position(s.min)] = '├';
+            cells[position(s.max)] = '┤';
+            cells[position(s.median)] = '│';
+
+            format!(
+                "{:label_width$} {}",
+                label,
+                cells.into_iter().collect::<String>().trim_end(),
+                label_width = label_width
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let (low, high) = (format_ns(low), format_ns(high));
+    lines.push(format!(
+        "{:label_width$} {}{:>width$}",
+        "",
+        low,
+        high,
+        label_width = label_width,
+        width = PLOT_WIDTH.saturating_sub(low.len())
+    ));
+
+    lines
+}
+
+fn scale(stats: &[(&str, BoxStats)]) -> Option<(u64, u64)> {
+    let low = stats.iter().map(|(_, s)| s.min).min()?;
+    let high = stats.iter().map(|(_, s)| s.max).max()?;
+
+    Some((low, high))
+}
+
+/// The histogram as a standalone SVG document.
+pub fn histogram_svg(title: &str, timings: &[u64]) -> String {
+    let (min, width, counts) = bins(timings);
+    let most = counts.iter().max().copied().unwrap_or(1).max(1);
+    let height = 320.0;
+    let plot_height = height - 2.0 * SVG_MARGIN;
+    let bar = (SVG_WIDTH - 2.0 * SVG_MARGIN) / counts.len().max(1) as f64;
+
+    let mut svg = svg_header(title, height);
+
+    for (i, &count) in counts.iter().enumerate() {
+        let bar_height = count as f64 / most as f64 * plot_height;
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="#4c78a8"><title>{} - {}: {}</title></rect>"##,
+            SVG_MARGIN + i as f64 * bar + 1.0,
+            height - SVG_MARGIN - bar_height,
+            (bar - 2.0).max(1.0),
+            bar_height,
+            format_ns(min + i as u64 * width),
+            format_ns(min + (i as u64 + 1) * width),
+            count
+        );
+    }
+
+    let max = min + counts.len() as u64 * width;
+    svg_axis(&mut svg, height - SVG_MARGIN, min, max);
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+/// The box plots as a standalone SVG document.
+pub fn box_plot_svg(title: &str, series: &[(&str, &[u64])]) -> String {
+    let stats = series
+        .iter()
+        .filter_map(|(label, timings)| box_stats(timings).map(|s| (*label, s)))
+        .collect::<Vec<_>>();
+    let row = 50.0;
+    let height = 2.0 * SVG_MARGIN + row * stats.len() as f64;
+    let (low, high) = scale(&stats).unwrap_or((0, 1));
+    let left = SVG_MARGIN * 2.0;
+    let x = |v: u64| {
+        left + (v - low) as f64 / (high - low).max(1) as f64 * (SVG_WIDTH - left - SVG_MARGIN)
+    };
+
+    let mut svg = svg_header(title, height);
+
+    for (i, (label, s)) in stats.iter().enumerate() {
+        let y = SVG_MARGIN + row * i as f64 + row / 2.0;
+        let _ = writeln!(
+            svg,
+            r##"<text x="{:.1}" y="{:.1}" text-anchor="end" dominant-baseline="middle">{}</text>"##,
+            left - 8.0,
+            y,
+            escape(label)
+        );
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="#333"/>"##,
+            x(s.min),
+            y,
+            x(s.max),
+            y
+        );
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="#4c78a8" stroke="#333"/>"##,
+            x(s.q1),
+            y - row / 4.0,
+            (x(s.q3) - x(s.q1)).max(1.0),
+            row / 2.0
+        );
+
+        for v in [s.min, s.median, s.max] {
+            let _ = writeln!(
+                svg,
+                r##"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="#333" stroke-width="2"/>"##,
+                x(v),
+                y - row / 4.0,
+                x(v),
+                y + row / 4.0
+            );
+        }
+    }
+
+    svg_axis(&mut svg, height - SVG_MARGIN, low, high);
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+fn svg_header(title: &str, height: f64) -> String {
+    format!(
+        concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">"##,
+            "\n",
+            r##"<rect width="100%" height="100%" fill="white"/>"##,
+            "\n",
+            r##"<text x="{x}" y="20" text-anchor="middle" font-size="14">{title}</text>"##,
+            "\n"
+        ),
+        w = SVG_WIDTH,
+        h = height,
+        x = SVG_WIDTH / 2.0,
+        title = escape(title)
+    )
+}
+
+fn svg_axis(svg: &mut String, y: f64, low: u64, high: u64) {
+    let _ = writeln!(
+        svg,
+        r##"<text x="{:.1}" y="{:.1}">{}</text>"##,
+        SVG_MARGIN,
+        y + 20.0,
+        format_ns(low)
+    );
+    let _ = writeln!(
+        svg,
+        r##"<text x="{:.1}" y="{:.1}" text-anchor="end">{}</text>"##,
+        SVG_WIDTH - SVG_MARGIN,
+        y + 20.0,
+        format_ns(high)
+    );
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_quartiles() {
+        let stats = box_stats(&[5, 1, 4, 2, 3]).unwrap();
+
+        assert_eq!(
+            stats,
+            BoxStats {
+                min: 1,
+                q1: 2,
+                median: 3,
+                q3: 4,
+                max: 5
+            }
+        );
+        assert!(box_stats(&[]).is_none());
+    }
+
+    #[test]
+    fn shows_both_peaks() {
+        let mut timings = vec![100; 10];
+        timings.extend(vec![200; 10]);
+
+        let (_, _, counts) = bins(&timings);
+        assert_eq!(counts.first(), Some(&10));
+        assert_eq!(counts.last(), Some(&10));
+        assert!(counts[1..counts.len() - 1].iter().all(|&c| c == 0));
+
+        assert_eq!(histogram(&[7, 7, 7]).len(), 1);
+    }
+
+    #[test]
+    fn plots_on_a_shared_scale() {
+        let a = [100, 110, 120, 130, 140];
+        let b = [140, 150, 160, 170, 180];
+        let lines = box_plots(&[("A", &a), ("B", &b)]);
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("A ├"));
+        assert!(lines[1].ends_with('┤'));
+        assert!(lines[0].chars().count() < lines[1].chars().count());
+    }
+}
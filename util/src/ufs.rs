@@ -0,0 +1,338 @@
+use std::env;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const MAX_FILE_NAME_LEN: usize = 255;
+const MIN_TASK_UID_LEN: usize = 8;
+const MAX_TASK_UID_LEN: usize = 64;
+
+/// Checks that a client supplied file name is a single plain path component.
+///
+/// Only ASCII letters, digits, `.`, `_` and `-` are accepted and the name may
+/// not start with a dot, so separators, `..`, drive prefixes and hidden files
+/// are all rejected.
+pub fn validate_file_name(file_name: &str) -> Result<&str, String> {
+    let is_valid = !file_name.is_empty()
+        && file_name.len() <= MAX_FILE_NAME_LEN
+        && !file_name.starts_with('.')
+        && file_name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'_' || b == b'-');
+
+    if is_valid {
+        Ok(file_name)
+    } else {
+        Err(format!("invalid file name: {:?}", file_name))
+    }
+}
+
+/// Checks that a task uid is safe to be used as a directory name.
+pub fn validate_task_uid(task_uid: &str) -> Result<&str, String> {
+    let is_valid = (MIN_TASK_UID_LEN..=MAX_TASK_UID_LEN).contains(&task_uid.len())
+        && task_uid
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-');
+
+    if is_valid {
+        Ok(task_uid)
+    } else {
+        Err(format!("invalid task uid: {:?}", task_uid))
+    }
+}
+
+/// A task workspace directory, removed with everything in it when dropped.
+#[derive(Debug)]
+pub struct TaskWorkspace {
+    path: PathBuf,
+}
+
+impl TaskWorkspace {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Deref for TaskWorkspace {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TaskWorkspace {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TaskWorkspace {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+static WORKSPACE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Creates a new empty workspace directory for one run of `task_uid` under
+/// `root`.
+///
+/// Every call gets its own directory, so concurrent runs of the same task
+/// never share files. Existing entries are never reused or followed, and the
+/// returned path is canonical and guaranteed to live inside `root`.
+pub fn create_task_workspace(root: &Path, task_uid: &str) -> Result<TaskWorkspace, String> {
+    let task_uid = validate_task_uid(task_uid)?;
+
+    fs::create_dir_all(root).map_err(|e| format!("unable to create {:?}, {}", root, e))?;
+
+    let root = root
+        .canonicalize()
+        .map_err(|e| format!("unable to resolve {:?}, {}", root, e))?;
+
+    let workspace = loop {
+        let workspace = root.join(format!(
+            "{}-{}-{}",
+            task_uid,
+            process::id(),
+            WORKSPACE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        match fs::create_dir(&workspace) {
+            Ok(()) => break TaskWorkspace { path: workspace },
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("unable to create {:?}, {}", workspace, e)),
+        }
+    };
+
+    let path = workspace
+        .path
+        .canonicalize()
+        .map_err(|e| format!("unable to resolve {:?}, {}", workspace.path, e))?;
+
+    if path.parent() != Some(root.as_path()) {
+        return Err(format!("workspace {:?} escapes {:?}", path, root));
+    }
+
+    Ok(workspace)
+}
+
+/// Resolves `file_name` inside `workspace` after validating it.
+pub fn workspace_file(workspace: &Path, file_name: &str) -> Result<PathBuf, String> {
+    Ok(workspace.join(validate_file_name(file_name)?))
+}
+
+/// Writes a new file inside `workspace`.
+///
+/// The file must not exist yet, so an existing file or symlink planted under
+/// the same name is never followed or overwritten.
+pub fn write_workspace_file(
+    workspace: &Path,
+    file_name: &str,
+    contents: &str,
+) -> Result<PathBuf, String> {
+    let path = workspace_file(workspace, file_name)?;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|e| format!("unable to write {}, {}", file_name, e))?;
+
+    Ok(path)
+}
+
+/// Checks that `path` names a program that can be started, bare names are
+/// looked up in `PATH`. Only the file is inspected, the program is not run.
+pub fn check_executable(path: &str) -> Result<(), String> {
+    if path.trim().is_empty() {
+        return Err("no executable given".to_string());
+    }
+
+    let program = Path::new(path);
+    let is_bare_name = program.components().count() == 1 && !program.has_root();
+
+    if is_bare_name {
+        let paths = env::var_os("PATH").unwrap_or_default();
+
+        if env::split_paths(&paths).any(|dir| is_executable(&dir.join(program))) {
+            return Ok(());
+        }
+
+        return Err(format!("{} is not in PATH", path));
+    }
+
+    if !program.exists() {
+        return Err(format!("{} does not exist", path));
+    }
+
+    if !program.is_file() {
+        return Err(format!("{} is not a file", path));
+    }
+
+    if !is_executable(program) {
+        return Err(format!("{} is not executable", path));
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file() || path.with_extension("exe").is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("qbm-ufs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn rejects_traversal_and_absolute_file_names() {
+        for name in [
+            "",
+            "..",
+            "../main.cpp",
+            "../../.bashrc",
+            "src/main.cpp",
+            "src\\main.cpp",
+            "/etc/passwd",
+            "C:\\Windows\\win.ini",
+            ".hidden.cpp",
+            "main .cpp",
+            "main\0.cpp",
+        ] {
+            assert!(validate_file_name(name).is_err(), "{:?} accepted", name);
+        }
+
+        assert_eq!(validate_file_name("main.cpp"), Ok("main.cpp"));
+        assert_eq!(validate_file_name("bench_1-a.cc"), Ok("bench_1-a.cc"));
+    }
+
+    #[test]
+    fn rejects_bad_task_uids() {
+        for uid in ["", "short", "../../../tmp", "abcdefgh/..", "αβγδεζηθικ"] {
+            assert!(validate_task_uid(uid).is_err(), "{:?} accepted", uid);
+        }
+
+        assert!(validate_task_uid("0123456789abcdef").is_ok());
+    }
+
+    #[test]
+    fn workspace_stays_inside_root() {
+        let root = scratch_dir("root");
+        let workspace = create_task_workspace(&root, "0123456789abcdef").unwrap();
+
+        assert!(workspace.starts_with(root.canonicalize().unwrap()));
+        assert!(create_task_workspace(&root, "../outside").is_err());
+
+        drop(workspace);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn workspaces_are_per_run_and_removed() {
+        let root = scratch_dir("per-run");
+        let first = create_task_workspace(&root, "0123456789abcdef").unwrap();
+        let second = create_task_workspace(&root, "0123456789abcdef").unwrap();
+
+        assert_ne!(first.path(), second.path());
+
+        write_workspace_file(&first, "main.cpp", "int main() {}").unwrap();
+        let path = first.to_path_buf();
+        drop(first);
+
+        assert!(!path.exists());
+        assert!(second.exists());
+
+        drop(second);
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn does_not_follow_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let root = scratch_dir("symlink");
+        let outside = scratch_dir("symlink-outside");
+        fs::create_dir_all(&outside).unwrap();
+
+        let workspace = create_task_workspace(&root, "0123456789abcdef").unwrap();
+        let victim = outside.join("victim.cpp");
+        fs::write(&victim, "original").unwrap();
+        symlink(&victim, workspace.join("main.cpp")).unwrap();
+
+        assert!(write_workspace_file(&workspace, "main.cpp", "evil").is_err());
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "original");
+
+        drop(workspace);
+        assert!(victim.exists());
+
+        // Other tests share the counter, so plant the next few names.
+        let next = WORKSPACE_COUNTER.load(Ordering::Relaxed);
+        for counter in next..next + 64 {
+            let name = format!("0123456789abcdef-{}-{}", std::process::id(), counter);
+            symlink(&outside, root.join(name)).unwrap();
+        }
+
+        let workspace = create_task_workspace(&root, "0123456789abcdef").unwrap();
+        assert!(workspace.starts_with(root.canonicalize().unwrap()));
+        assert!(!fs::symlink_metadata(&*workspace).unwrap().is_symlink());
+        assert_eq!(fs::read_dir(&outside).unwrap().count(), 1);
+        assert!(victim.exists());
+
+        drop(workspace);
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn checks_executables() {
+        let dir = scratch_dir("executable");
+        fs::create_dir_all(&dir).unwrap();
+
+        let missing = dir.join("missing");
+        assert!(check_executable(missing.to_str().unwrap())
+            .unwrap_err()
+            .contains("does not exist"));
+        assert!(check_executable(dir.to_str().unwrap())
+            .unwrap_err()
+            .contains("is not a file"));
+        assert!(check_executable("qbm-no-such-compiler")
+            .unwrap_err()
+            .contains("is not in PATH"));
+        assert!(check_executable(" ").is_err());
+        assert!(check_executable("sh").is_ok());
+
+        #[cfg(unix)]
+        {
+            let script = dir.join("script");
+            fs::write(&script, "#!/bin/sh\n").unwrap();
+            assert!(check_executable(script.to_str().unwrap())
+                .unwrap_err()
+                .contains("is not executable"));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
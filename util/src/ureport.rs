@@ -0,0 +1,391 @@
+use crate::uchart::{box_plot_svg, box_stats, escape, format_ns, histogram_svg};
+use crate::ustat::{mean, variance};
+use crate::uto::{BenchmarkInfo, TaskRecord};
+use std::fmt::Write;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em auto; max-width: 960px; color: #222; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+th { background: #f4f4f4; }
+td.num { text-align: right; font-family: monospace; }
+pre { background: #f8f8f8; border: 1px solid #ddd; padding: 1em; overflow-x: auto; }
+.regression { color: #b00; font-weight: bold; }
+.warning { color: #a60; }
+section { border-top: 2px solid #ddd; margin-top: 2em; }
+";
+
+/// A self-contained HTML page describing `records`, charts are inline SVG.
+pub fn html_report(records: &[TaskRecord]) -> String {
+    let mut html = String::new();
+
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Benchmark report</title>\n<style>{}</style>\n</head>\n<body>\n<h1>Benchmark report</h1>\n",
+        STYLE
+    );
+
+    summary_table(&mut html, records);
+
+    if records
+        .iter()
+        .filter(|r| !r.result.timings.is_empty())
+        .count()
+        > 1
+    {
+        let labels = records.iter().map(label).collect::<Vec<_>>();
+        let series = records
+            .iter()
+            .zip(&labels)
+            .map(|(r, l)| (l.as_str(), r.result.timings.as_slice()))
+            .collect::<Vec<_>>();
+
+        html.push_str(&box_plot_svg("Timings", &series));
+    }
+
+    for record in records {
+        record_section(&mut html, record);
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Benchmark name or the start of the task uid.
+fn label(record: &TaskRecord) -> String {
+    match &record.name {
+        Some(name) => format!("{} ({})", name, short_uid(&record.task_uid)),
+        None => short_uid(&record.task_uid).to_string(),
+    }
+}
+
+fn short_uid(task_uid: &str) -> &str {
+    task_uid.get(..8).unwrap_or(task_uid)
+}
+
+struct Statistics {
+    mean: u64,
+    stddev: u64,
+    median: u64,
+    min: u64,
+    max: u64,
+}
+
+fn statistics(info: &BenchmarkInfo) -> Option<Statistics> {
+    let stats = box_stats(&info.timings)?;
+    let samples = info.timings.iter().map(|&t| t as f64).collect::<Vec<_>>();
+
+    Some(Statistics {
+        mean: mean(&samples) as u64,
+        stddev: variance(&samples).sqrt() as u64,
+        median: stats.median,
+        min: stats.min,
+        max: stats.max,
+    })
+}
+
+fn summary_table(html: &mut String, records: &[TaskRecord]) {
+    html.push_str("<table>\n<tr><th>Benchmark</th><th>Compiler</th><th>Flags</th><th>Iterations</th><th>Mean</th><th>Std. dev.</th><th>Median</th><th>Min</th><th>Max</th><th>Baseline</th></tr>\n");
+
+    for record in records {
+        let _ = write!(
+            html,
+            "<tr><td><a href=\"#{}\">{}</a></td><td>{} {}</td><td><code>{}</code></td><td class=\"num\">{}</td>",
+            escape(&record.task_uid),
+            escape(&label(record)),
+            escape(&record.compiler),
+            escape(&record.compiler_version),
+            escape(&record.compiler_args),
+            record.result.timings.len()
+        );
+
+        match statistics(&record.result) {
+            Some(s) => {
+                for value in [s.mean, s.stddev, s.median, s.min, s.max] {
+                    let _ = write!(html, "<td class=\"num\">{}</td>", format_ns(value));
+                }
+            }
+            None => html.push_str("<td colspan=\"5\">failed</td>"),
+        }
+
+        match &record.result.comparison {
+            Some(c) if c.regression => {
+                let _ = write!(
+                    html,
+                    "<td class=\"num regression\">{:+.1}%</td>",
+                    c.change * 100.0
+                );
+            }
+            Some(c) => {
+                let _ = write!(html, "<td class=\"num\">{:+.1}%</td>", c.change * 100.0);
+            }
+            None => html.push_str("<td></td>"),
+        }
+
+        html.push_str("</tr>\n");
+    }
+
+    html.push_str("</table>\n");
+}
+
+fn record_section(html: &mut String, record: &TaskRecord) {
+    let result = &record.result;
+    let created_at = OffsetDateTime::from_unix_timestamp(record.created_at)
+        .ok()
+        .and_then(|t| t.format(&Rfc3339).ok())
+        .unwrap_or_else(|| record.created_at.to_string());
+
+    let _ = write!(
+        html,
+        "<section id=\"{}\">\n<h2>{}</h2>\n",
+        escape(&record.task_uid),
+        escape(&label(record))
+    );
+
+    let mut rows = vec![
+        ("Task", record.task_uid.clone()),
+        ("Created", created_at),
+        (
+            "Compiler",
+            format!("{} {}", record.compiler, record.compiler_version),
+        ),
+        ("Flags", record.compiler_args.clone()),
+        ("Iterations", record.iterations.to_string()),
+    ];
+
+    if let Some(user) = &record.user {
+        rows.push(("User", user.clone()));
+    }
+
+    if !record.tags.is_empty() {
+        rows.push(("Tags", record.tags.join(", ")));
+    }
+
+    if let Some(node) = &result.node {
+        rows.push(("Node", node.clone()));
+    }
+
+    key_value_table(html, &rows);
+
+    if let Some(fingerprint) = &result.fingerprint {
+        let setting = |s: Option<bool>| match s {
+            Some(true) => "on".to_string(),
+            Some(false) => "off".to_string(),
+            None => "unknown".to_string(),
+        };
+
+        html.push_str("<h3>Environment</h3>\n");
+        key_value_table(
+            html,
+            &[
+                ("Fingerprint", fingerprint.id()),
+                ("Host", fingerprint.host_name.clone()),
+                (
+                    "CPU",
+                    format!(
+                        "{} {} ({} cores / {} threads)",
+                        fingerprint.cpu_vendor,
+                        fingerprint.cpu_brand,
+                        fingerprint.cpu_cores,
+                        fingerprint.cpu_logical_cores
+                    ),
+                ),
+                (
+                    "Governor",
+                    fingerprint
+                        .governor
+                        .clone()
+                        .unwrap_or_else(|| "unknown".to_string()),
+                ),
+                ("Turbo", setting(fingerprint.turbo)),
+                ("SMT", setting(fingerprint.smt)),
+                (
+                    "ASLR",
+                    fingerprint
+                        .aslr
+                        .map(|a| a.to_string())
+                        .unwrap_or_else(|| "unknown".to_string()),
+                ),
+                ("Kernel", fingerprint.kernel_version.clone()),
+                (
+                    "Load",
+                    format!(
+                        "{:.2} {:.2} {:.2}",
+                        fingerprint.load_average[0],
+                        fingerprint.load_average[1],
+                        fingerprint.load_average[2]
+                    ),
+                ),
+                (
+                    "Memory used",
+                    format!("{:.0}%", fingerprint.memory_used * 100.0),
+                ),
+            ],
+        );
+    } else if let Some(env) = &record.environment {
+        html.push_str("<h3>Environment</h3>\n");
+        key_value_table(
+            html,
+            &[
+                ("Platform", format!("{} {}", env.platform, env.os_version)),
+                ("Kernel", env.kernel_version.clone()),
+                (
+                    "CPU",
+                    format!(
+                        "{} ({} cores / {} threads)",
+                        env.cpu_brand.trim(),
+                        env.cpu_cores,
+                        env.cpu_logical_cores
+                    ),
+                ),
+                ("Memory", env.total_memory.clone()),
+            ],
+        );
+    }
+
+    html.push_str("<h3>Statistics</h3>\n");
+
+    match statistics(result) {
+        Some(s) => {
+            key_value_table(
+                html,
+                &[
+                    ("Mean", format_ns(s.mean)),
+                    ("Std. dev.", format_ns(s.stddev)),
+                    ("Median", format_ns(s.median)),
+                    ("Min", format_ns(s.min)),
+                    ("Max", format_ns(s.max)),
+                ],
+            );
+            html.push_str(&histogram_svg("Per-iteration timings", &result.timings));
+        }
+        None => html.push_str("<p>No timings, the task failed.</p>\n"),
+    }
+
+    if let Some(comparison) = &result.comparison {
+        let _ = writeln!(
+            html,
+            "<p class=\"{}\">{:+.1}% vs baseline <a href=\"#{}\">{}</a>{}{}</p>",
+            if comparison.regression {
+                "regression"
+            } else {
+                ""
+            },
+            comparison.change * 100.0,
+            escape(&comparison.baseline_uid),
+            escape(short_uid(&comparison.baseline_uid)),
+            comparison
+                .p_value
+                .map(|p| format!(", p={:.4}", p))
+                .unwrap_or_default(),
+            if comparison.regression {
+                ", regression"
+            } else {
+                ""
+            }
+        );
+    }
+
+    for warning in result
+        .warnings
+        .iter()
+        .chain(result.comparison.iter().flat_map(|c| c.warnings.iter()))
+    {
+        let _ = writeln!(
+            html,
+            "<p class=\"warning\">warning: {}</p>",
+            escape(warning)
+        );
+    }
+
+    let _ = write!(
+        html,
+        "<h3>{}</h3>\n<pre><code>{}</code></pre>\n",
+        escape(&record.source_file),
+        escape(&record.source_code)
+    );
+
+    if !result.message.trim().is_empty() {
+        let _ = write!(
+            html,
+            "<h3>Compiler output</h3>\n<pre>{}</pre>\n",
+            escape(&result.message)
+        );
+    }
+
+    if !result.output.is_empty() {
+        let _ = write!(
+            html,
+            "<h3>Program output</h3>\n<pre>{}</pre>\n",
+            escape(&result.output)
+        );
+    }
+
+    html.push_str("</section>\n");
+}
+
+fn key_value_table(html: &mut String, rows: &[(&str, String)]) {
+    html.push_str("<table>\n");
+
+    for (key, value) in rows {
+        let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", key, escape(value));
+    }
+
+    html.push_str("</table>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(task_uid: &str, timings: Vec<u64>) -> TaskRecord {
+        TaskRecord {
+            task_uid: task_uid.to_string(),
+            compiler: "g++".to_string(),
+            compiler_version: "11.2.0".to_string(),
+            source_file: "main.cpp".to_string(),
+            source_code: "int main() { return 1 < 2; }".to_string(),
+            compiler_args: "-O2".to_string(),
+            assembly: false,
+            iterations: timings.len() as u32,
+            name: Some("sort".to_string()),
+            user: None,
+            tags: vec![],
+            created_at: 0,
+            environment: None,
+            result: BenchmarkInfo {
+                timings,
+                ..BenchmarkInfo::new(task_uid)
+            },
+        }
+    }
+
+    #[test]
+    fn embeds_everything() {
+        let html = html_report(&[
+            record("a1b2c3d4-0000", vec![100, 110, 120]),
+            record("e5f6a7b8-0000", vec![]),
+        ]);
+
+        assert!(html.contains("<section id=\"a1b2c3d4-0000\">"));
+        assert!(html.contains("1 &lt; 2"));
+        assert!(html.contains("110 ns"));
+        assert!(html.contains("the task failed"));
+        // One histogram, no box plot for a single successful result.
+        assert_eq!(html.matches("<svg").count(), 1);
+        assert!(!html.contains("<script"));
+    }
+
+    #[test]
+    fn compares_several_results() {
+        let html = html_report(&[
+            record("a1b2c3d4-0000", vec![100, 110, 120]),
+            record("e5f6a7b8-0000", vec![200, 210, 220]),
+        ]);
+
+        assert_eq!(html.matches("<svg").count(), 3);
+        assert!(html.contains("sort (e5f6a7b8)"));
+    }
+}
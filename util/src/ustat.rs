@@ -0,0 +1,167 @@
+pub fn mean(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+/// Unbiased sample variance.
+pub fn variance(samples: &[f64]) -> f64 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+
+    let mean = mean(samples);
+
+    samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (samples.len() - 1) as f64
+}
+
+/// One-sided Welch's t-test, returns the p-value of `b` having a larger mean
+/// than `a`. At least two samples are needed on each side.
+pub fn welch_t_test(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+
+    let (na, nb) = (a.len() as f64, b.len() as f64);
+    let (va, vb) = (variance(a) / na, variance(b) / nb);
+    let difference = mean(b) - mean(a);
+
+    if va + vb == 0.0 {
+        return Some(if difference > 0.0 { 0.0 } else { 1.0 });
+    }
+
+    let t = difference / (va + vb).sqrt();
+    let df = (va + vb).powi(2) / (va.powi(2) / (na - 1.0) + vb.powi(2) / (nb - 1.0));
+
+    Some(1.0 - student_t_cdf(t, df))
+}
+
+fn student_t_cdf(t: f64, df: f64) -> f64 {
+    let tail = 0.5 * regularized_incomplete_beta(df / (df + t * t), df / 2.0, 0.5);
+
+    if t > 0.0 {
+        1.0 - tail
+    } else {
+        tail
+    }
+}
+
+fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+
+    if x >= 1.0 {
+        return 1.0;
+    }
+
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+
+    // The continued fraction converges quickly only below the mean.
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+/// Lentz's method, see Numerical Recipes 6.4.
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const EPSILON: f64 = 1e-14;
+    const TINY: f64 = 1e-300;
+
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    d = 1.0 / if d.abs() < TINY { TINY } else { d };
+    let mut h = d;
+
+    for m in 1..300 {
+        let m = m as f64;
+
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            d = 1.0 / if d.abs() < TINY { TINY } else { d };
+            c = 1.0 + numerator / c;
+            c = if c.abs() < TINY { TINY } else { c };
+            h *= d * c;
+        }
+
+        if (d * c - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+
+    h
+}
+
+/// Lanczos approximation, g = 7.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |s, (i, c)| s + c / (x + i as f64 + 1.0));
+
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn computes_moments() {
+        assert!(close(mean(&[1.0, 2.0, 3.0, 4.0]), 2.5));
+        assert!(close(variance(&[1.0, 2.0, 3.0, 4.0]), 1.666_666));
+        assert_eq!(mean(&[]), 0.0);
+    }
+
+    #[test]
+    fn matches_student_t_table() {
+        // Two-sided 95% critical values.
+        assert!(close(student_t_cdf(2.228, 10.0), 0.975));
+        assert!(close(student_t_cdf(12.706, 1.0), 0.975));
+        assert!(close(student_t_cdf(0.0, 5.0), 0.5));
+        assert!(close(student_t_cdf(-2.571, 5.0), 0.025));
+    }
+
+    #[test]
+    fn detects_slower_samples() {
+        let baseline = [100.0, 101.0, 99.0, 100.5, 99.5];
+        let slower = [110.0, 111.0, 109.0, 110.5, 109.5];
+        let same = [100.2, 100.8, 99.1, 100.4, 99.6];
+
+        assert!(welch_t_test(&baseline, &slower).unwrap() < 0.001);
+        assert!(welch_t_test(&slower, &baseline).unwrap() > 0.999);
+        assert!(welch_t_test(&baseline, &same).unwrap() > 0.05);
+        assert!(welch_t_test(&baseline, &[1.0]).is_none());
+    }
+}
@@ -1,12 +1,18 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone)]
+/// Version of the wire types, bumped when a change is not backwards compatible.
+pub const QBM_PROTOCOL_VERSION: u32 = 1;
+/// Oldest protocol version servers still accept.
+pub const QBM_MIN_PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
 pub struct ProcessorInfo {
     pub cpu_name: String,
     pub cpu_freq: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
 pub struct RemoteServerInfo {
     pub platform: String,
     pub kernel_version: String,
@@ -18,9 +24,12 @@ pub struct RemoteServerInfo {
     pub cpu_vendor: String,
     pub cpu_brand: String,
     pub cpus: Vec<ProcessorInfo>,
+    /// Reasons the host is not ready for benchmarking right now.
+    #[serde(default)]
+    pub readiness: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
 pub struct CompilerInfo {
     pub name: String,
     pub version: String,
@@ -28,20 +37,393 @@ pub struct CompilerInfo {
     pub thread_model: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
 pub struct BenchmarkInfo {
     pub task_uid: String,
     pub message: String,
     pub output: String,
+    /// Wall clock time of every iteration in nanoseconds.
+    #[serde(default)]
+    pub timings: Vec<u64>,
+    #[serde(default)]
+    pub comparison: Option<BaselineComparison>,
+    #[serde(default)]
+    pub fingerprint: Option<EnvironmentFingerprint>,
+    /// Readiness checks the host failed when the task ran and other things
+    /// that make the timings less trustworthy.
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Address of the worker that ran the task in cluster mode.
+    #[serde(default)]
+    pub node: Option<String>,
+}
+
+impl BenchmarkInfo {
+    /// A result without any runs yet.
+    pub fn new(task_uid: &str) -> BenchmarkInfo {
+        BenchmarkInfo {
+            task_uid: task_uid.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// A task that did not run, `message` says why.
+    pub fn failed(task_uid: &str, message: String) -> BenchmarkInfo {
+        BenchmarkInfo {
+            message,
+            ..BenchmarkInfo::new(task_uid)
+        }
+    }
+}
+
+/// State of the host right before a task's first iteration.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct EnvironmentFingerprint {
+    pub host_name: String,
+    pub cpu_vendor: String,
+    pub cpu_brand: String,
+    pub cpu_cores: u64,
+    pub cpu_logical_cores: u64,
+    /// `None` wherever the platform does not expose the setting.
+    pub governor: Option<String>,
+    pub turbo: Option<bool>,
+    pub smt: Option<bool>,
+    #[serde(default)]
+    pub aslr: Option<u8>,
+    pub kernel_version: String,
+    pub compiler: String,
+    pub compiler_version: String,
+    /// 1, 5 and 15 minute load averages.
+    pub load_average: [f64; 3],
+    /// Share of memory in use, `0.25` is 25%.
+    pub memory_used: f64,
+    /// `some avg10` of `/proc/pressure/memory`.
+    pub memory_pressure: Option<f64>,
+}
+
+impl EnvironmentFingerprint {
+    /// Short hash of the machine and its configuration, load and memory are
+    /// left out as they change from run to run.
+    pub fn id(&self) -> String {
+        // FNV-1a, unlike `DefaultHasher` it is stable across Rust releases.
+        let hash = self
+            .stable_fields()
+            .iter()
+            .flat_map(|(_, value)| value.bytes().chain([0]))
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            });
+
+        format!("{:016x}", hash)
+    }
+
+    /// Human readable differences in machine and configuration.
+    pub fn differences(&self, other: &EnvironmentFingerprint) -> Vec<String> {
+        self.compared_fields()
+            .into_iter()
+            .zip(other.compared_fields())
+            .filter(|((_, a), (_, b))| a != b)
+            .map(|((field, a), (_, b))| format!("{} differs: {} vs {}", field, a, b))
+            .collect()
+    }
+
+    /// A probed compiler name already ends with its version, older records
+    /// hold the configured name and version.
+    fn compiler_identity(&self) -> String {
+        if self.compiler.contains(&self.compiler_version) {
+            self.compiler.clone()
+        } else {
+            format!("{} {}", self.compiler, self.compiler_version)
+        }
+    }
+
+    /// Stable fields plus those recorded after `id()` was introduced, which
+    /// would change the id of older records.
+    fn compared_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = self.stable_fields();
+        fields.push((
+            "aslr",
+            self.aslr
+                .map(|a| a.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+        ));
+
+        fields
+    }
+
+    fn stable_fields(&self) -> Vec<(&'static str, String)> {
+        let setting = |s: Option<bool>| match s {
+            Some(true) => "on".to_string(),
+            Some(false) => "off".to_string(),
+            None => "unknown".to_string(),
+        };
+
+        vec![
+            ("host", self.host_name.clone()),
+            (
+                "cpu",
+                format!("{} {}", self.cpu_vendor, self.cpu_brand.trim()),
+            ),
+            (
+                "cores",
+                format!("{}/{}", self.cpu_cores, self.cpu_logical_cores),
+            ),
+            (
+                "governor",
+                self.governor
+                    .clone()
+                    .unwrap_or_else(|| "unknown".to_string()),
+            ),
+            ("turbo", setting(self.turbo)),
+            ("smt", setting(self.smt)),
+            ("kernel", self.kernel_version.clone()),
+            ("compiler", self.compiler_identity()),
+        ]
+    }
+}
+
+/// A result compared against the baseline of its benchmark name.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct BaselineComparison {
+    pub baseline_uid: String,
+    pub baseline_mean_ns: f64,
+    pub mean_ns: f64,
+    /// Relative change of the mean, `0.1` means 10% slower.
+    pub change: f64,
+    /// One-sided Welch's t-test, `None` with fewer than two iterations.
+    pub p_value: Option<f64>,
+    pub regression: bool,
+    /// Set when the baseline was measured on a different machine or
+    /// configuration.
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct BenchmarkTask<'r> {
-    pub task_uid: &'r str,
-    pub compiler: &'r str,
-    pub compiler_version: &'r str,
-    pub source_file: &'r str,
-    pub source_code: &'r str,
-    pub compiler_args: &'r str,
+/// A benchmark request. Fields added after protocol version 1 need serde
+/// defaults so older clients keep working, unknown fields are ignored.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct BenchmarkTask {
+    #[serde(default = "default_protocol_version")]
+    pub protocol_version: u32,
+    pub task_uid: String,
+    pub compiler: String,
+    pub compiler_version: String,
+    pub source_file: String,
+    pub source_code: String,
+    #[serde(default)]
+    pub compiler_args: String,
+    #[serde(default)]
     pub assembly: bool,
+    #[serde(default = "default_iterations")]
+    pub iterations: u32,
+    /// Benchmark name results are compared by against a baseline.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub constraints: NodeConstraints,
+}
+
+/// Which workers a coordinator may send a task to, the CPU fields are matched
+/// as case insensitive substrings of the worker's `RemoteServerInfo`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
+pub struct NodeConstraints {
+    pub cpu_vendor: Option<String>,
+    pub cpu_brand: Option<String>,
+    /// Address of the one worker the task has to run on.
+    #[serde(default)]
+    pub node: Option<String>,
+}
+
+impl NodeConstraints {
+    pub fn matches(&self, node: &NodeInfo) -> bool {
+        let environment = &node.environment;
+        let contains = |value: &str, pattern: &Option<String>| {
+            pattern
+                .as_ref()
+                .is_none_or(|p| value.to_lowercase().contains(&p.to_lowercase()))
+        };
+
+        contains(&environment.cpu_vendor, &self.cpu_vendor)
+            && contains(&environment.cpu_brand, &self.cpu_brand)
+            && self.node.as_ref().is_none_or(|a| a == &node.address)
+    }
+}
+
+/// A worker as announced to the coordinator with every heartbeat.
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct NodeInfo {
+    /// Where the coordinator reaches the worker, e.g. `10.0.0.2:9000`.
+    pub address: String,
+    pub environment: RemoteServerInfo,
+    pub compilers: Vec<CompilerInfo>,
+}
+
+/// A finished task as kept in the server's result store.
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct TaskRecord {
+    pub task_uid: String,
+    pub compiler: String,
+    pub compiler_version: String,
+    pub source_file: String,
+    pub source_code: String,
+    pub compiler_args: String,
+    pub assembly: bool,
+    #[serde(default = "default_iterations")]
+    pub iterations: u32,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub user: Option<String>,
+    pub tags: Vec<String>,
+    /// Unix timestamp in seconds.
+    pub created_at: i64,
+    #[serde(default)]
+    pub environment: Option<RemoteServerInfo>,
+    pub result: BenchmarkInfo,
+}
+
+/// Progress of a task as streamed by `GET /tasks/<task_uid>/events`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TaskEvent {
+    Queued,
+    Compiling,
+    Compiled {
+        output: String,
+    },
+    Iteration {
+        iteration: u32,
+        iterations: u32,
+        elapsed_ns: u64,
+    },
+    /// Stdout of a run while it arrives, a run may send several.
+    Output {
+        #[serde(default)]
+        iteration: u32,
+        output: String,
+    },
+    Done {
+        result: Box<BenchmarkInfo>,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Queued,
+    Compiling,
+    Running,
+}
+
+/// A task that is not done yet, as listed by `GET /tasks`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ActiveTask {
+    pub task_uid: String,
+    pub state: TaskState,
+    /// Finished iterations, only counted while `Running`.
+    pub iteration: u32,
+    pub iterations: u32,
+}
+
+/// Answer of `GET /handshake`, the protocol versions a server accepts.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Handshake {
+    pub protocol_version: u32,
+    #[serde(default = "default_protocol_version")]
+    pub min_protocol_version: u32,
+    #[serde(default)]
+    pub server_version: String,
+}
+
+impl Handshake {
+    /// Whether a peer speaking `protocol_version` can talk to this server.
+    pub fn check(&self, protocol_version: u32) -> Result<(), String> {
+        if protocol_version > self.protocol_version {
+            Err(format!(
+                "protocol version {} is newer than server {} supports (up to {}), upgrade the server",
+                protocol_version, self.server_version, self.protocol_version
+            ))
+        } else if protocol_version < self.min_protocol_version {
+            Err(format!(
+                "protocol version {} is no longer supported by server {}, which needs {} or newer, upgrade the client",
+                protocol_version, self.server_version, self.min_protocol_version
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn default_iterations() -> u32 {
+    1
+}
+
+/// Peers that do not send a version predate it and speak version 1.
+fn default_protocol_version() -> u32 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_tasks_of_other_versions() {
+        let old = r#"{"task_uid": "a1b2c3d4", "compiler": "g++", "compiler_version": "11.2.0",
+            "source_file": "main.cpp", "source_code": "int main() {}", "compiler_args": "-O2",
+            "assembly": false}"#;
+        let task = serde_json::from_str::<BenchmarkTask>(old).unwrap();
+
+        assert_eq!(task.protocol_version, 1);
+        assert_eq!(task.iterations, 1);
+
+        let newer = r#"{"protocol_version": 7, "task_uid": "a1b2c3d4", "compiler": "g++",
+            "compiler_version": "11.2.0", "source_file": "main.cpp", "source_code": "",
+            "priority": "high"}"#;
+        let task = serde_json::from_str::<BenchmarkTask>(newer).unwrap();
+
+        assert_eq!(task.protocol_version, 7);
+        assert_eq!(task.compiler_args, "");
+    }
+
+    #[test]
+    fn keeps_ids_of_older_fingerprints() {
+        let old = r#"{"host_name": "bench-1", "cpu_vendor": "GenuineIntel",
+            "cpu_brand": "Intel(R) Core(TM) i7-8700", "cpu_cores": 6, "cpu_logical_cores": 12,
+            "governor": "performance", "turbo": false, "smt": true, "kernel_version": "5.14.0",
+            "compiler": "g++", "compiler_version": "11.2.0", "load_average": [0.1, 0.1, 0.1],
+            "memory_used": 0.2, "memory_pressure": null}"#;
+        let old = serde_json::from_str::<EnvironmentFingerprint>(old).unwrap();
+        let new = EnvironmentFingerprint {
+            aslr: Some(2),
+            ..old.clone()
+        };
+
+        assert_eq!(old.id(), "5703092fc694eb9d");
+        assert_eq!(new.id(), old.id());
+        assert_eq!(new.differences(&old), ["aslr differs: 2 vs unknown"]);
+    }
+
+    #[test]
+    fn checks_protocol_versions() {
+        let handshake = Handshake {
+            protocol_version: 3,
+            min_protocol_version: 2,
+            server_version: "0.2.0".to_string(),
+        };
+
+        assert!(handshake.check(2).is_ok());
+        assert!(handshake.check(3).is_ok());
+        assert!(handshake
+            .check(1)
+            .unwrap_err()
+            .contains("upgrade the client"));
+        assert!(handshake
+            .check(4)
+            .unwrap_err()
+            .contains("upgrade the server"));
+    }
 }